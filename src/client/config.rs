@@ -13,6 +13,18 @@ pub struct ClientConfig {
     pub user_agent: Option<String>,
     pub proxies: Vec<ProxyConfig>,
     pub use_cache: UseCacheConfig,
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+    /// per upstream request, reading the body included; past it the next provider is tried
+    #[serde(with="crate::utils::duration_fmt", default="default_request_timeout")]
+    pub request_timeout: Duration,
+    /// tried in order, the next one on a 429, a 5xx, a timeout, a connection error or an answer that is not a profile; just `upstream` if empty
+    #[serde(default)]
+    pub providers: Vec<ProviderConfig>,
+}
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(10)
 }
 
 impl Default for ClientConfig {
@@ -23,12 +35,58 @@ impl Default for ClientConfig {
             timeout: Duration::from_millis(5000),
             user_agent: None,
             proxies: Vec::new(),
-            use_cache: UseCacheConfig::default()
+            use_cache: UseCacheConfig::default(),
+            upstream: UpstreamConfig::default(),
+            request_timeout: default_request_timeout(),
+            providers: Vec::new(),
         }
     }
 }
 
 
+/// where the upstream requests go; point them at a mock, a caching mirror or a Yggdrasil server (for authlib-injector, `<root>/sessionserver` and `<root>/api`)
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct UpstreamConfig {
+    /// base of `/session/minecraft/profile/<uuid>`
+    pub session_server: String,
+    /// base of `/users/profiles/minecraft/<name>`
+    pub api_server: String,
+}
+
+impl Default for UpstreamConfig {
+
+    fn default() -> Self {
+        Self {
+            session_server: "https://sessionserver.mojang.com".to_string(),
+            api_server: "https://api.mojang.com".to_string(),
+        }
+    }
+}
+
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProviderConfig {
+    /// the Mojang API layout, as also served by Yggdrasil servers; see `UpstreamConfig`
+    Yggdrasil {
+        /// the `provider` of logs and metrics
+        name: String,
+        session_server: String,
+        api_server: String,
+    },
+    /// a JSON API answering with Mojang shaped profiles; `{uuid}`, `{id}` (the uuid without hyphens) and `{name}` in the urls are replaced
+    Json {
+        name: String,
+        profile_url: String,
+        uuid_url: String,
+        /// JSON pointer to the profile in a wrapped answer, like `/data/player`
+        #[serde(default)]
+        pointer: Option<String>,
+    },
+}
+
+
 #[derive(Debug,Serialize,Deserialize)]
 pub struct ProxyConfig {
     pub address: SocketAddr,
@@ -43,11 +101,22 @@ pub struct ProxyAuthorizationConfig {
 
 
 #[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
 pub struct UseCacheConfig {
     #[serde(with="crate::utils::duration_fmt")]
     pub unchanged: Duration,
     #[serde(with="crate::utils::duration_fmt")]
     pub changed: Duration,
+    /// for profiles upstream does not know
+    #[serde(with="crate::utils::duration_fmt")]
+    pub missing: Duration,
+    /// how long past `unchanged` / `changed` a history is still served right away while it is refreshed in the background;
+    /// zero to always wait for upstream
+    #[serde(with="crate::utils::duration_fmt")]
+    pub stale_while_revalidate: Duration,
+    /// how long after the last successful upstream check a history is still served when upstream fails
+    #[serde(with="crate::utils::duration_fmt")]
+    pub max_stale: Duration,
 }
 
 impl Default for UseCacheConfig {
@@ -55,7 +124,10 @@ impl Default for UseCacheConfig {
     fn default() -> Self {
         Self { 
             unchanged: Duration::from_secs(12 * 3600), 
-            changed: Duration::from_secs(30 * 24 * 3600)
+            changed: Duration::from_secs(30 * 24 * 3600),
+            missing: Duration::from_secs(3600),
+            stale_while_revalidate: Duration::ZERO,
+            max_stale: Duration::from_secs(7 * 24 * 3600),
         }
     }
 }
\ No newline at end of file
@@ -16,9 +16,19 @@ pub struct Profile {
 
      pub name: String,
 
+     /// some mirrors leave the textures out
+     #[serde(default)]
      pub properties: Vec<Properity>
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ProfileId {
+
+     pub id: Uuid,
+
+     pub name: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Properity {
 
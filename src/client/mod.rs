@@ -1,14 +1,20 @@
+use std::collections::VecDeque;
+use std::fmt;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
 
+use futures_util::future::BoxFuture;
 use headers::Authorization;
 use hyper::Client;
 use hyper::Method;
 use hyper::Request;
 use hyper::Body;
 use hyper::StatusCode;
+use hyper::Uri;
 use hyper::body;
-use hyper::body::Buf;
+use hyper::body::Bytes;
 use hyper::client::ResponseFuture;
 use hyper::client::connect::Connect;
 use hyper::header;
@@ -17,15 +23,27 @@ use hyper_proxy::Intercept;
 use hyper_proxy::Proxy;
 use hyper_proxy::ProxyConnector;
 use hyper_tls::HttpsConnector;
+use serde::de::DeserializeOwned;
 use uuid::Uuid;
 
+use crate::metrics::Metrics;
 
 use self::config::ClientConfig;
 use self::config::ProxyConfig;
+use self::config::UpstreamConfig;
 use self::data::Profile;
+use self::data::ProfileId;
+use self::provider::ProfileProvider;
+use self::provider::YggdrasilProvider;
+use self::provider::build_provider;
 
 pub mod data;
 pub mod config;
+pub mod provider;
+
+/// how many of the latest upstream outcomes `success_rate` looks at, and for how long
+const OUTCOME_WINDOW: usize = 100;
+const OUTCOME_MAX_AGE: Duration = Duration::from_secs(5 * 60);
 
 trait GeneralClient {
     fn request(&self, req: Request<Body>) -> ResponseFuture;
@@ -52,6 +70,31 @@ pub enum JsonRequesterError {
     Deserialize(serde_json::Error),
     Hyper(hyper::Error),
     StatusCode(StatusCode),
+    /// no answer within `ClientConfig::request_timeout`
+    Timeout,
+}
+
+impl JsonRequesterError {
+
+    /// whether the next provider may do better: rate limits, server errors, timeouts, unreachable providers and answers that are not a profile
+    pub fn should_fall_back(&self) -> bool {
+        match self {
+            Self::StatusCode(s) => *s == StatusCode::TOO_MANY_REQUESTS || s.is_server_error(),
+            Self::Hyper(_) | Self::Timeout | Self::Deserialize(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for JsonRequesterError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deserialize(e) => write!(f, "deserialize: {}", e),
+            Self::Hyper(e) => write!(f, "request: {}", e),
+            Self::StatusCode(s) => write!(f, "status: {}", s),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
 }
 
 impl From<serde_json::Error> for JsonRequesterError {
@@ -69,14 +112,76 @@ impl From<hyper::Error> for JsonRequesterError {
 }
 
 
+/// the http client every provider sends through, with the metrics and the outcomes behind `success_rate`
+struct HttpSender {
+    client: Arc<dyn GeneralClient + Send + Sync>,
+    timeout: Duration,
+    metrics: Metrics,
+    outcomes: Mutex<VecDeque<(Instant, bool)>>,
+}
+
+impl HttpSender {
+
+    fn record_outcome(&self, ok: bool) {
+        let now = Instant::now();
+        let mut outcomes = self.outcomes.lock().unwrap();
+        prune_outcomes(&mut outcomes, now);
+        if outcomes.len() >= OUTCOME_WINDOW {
+            outcomes.pop_front();
+        }
+        outcomes.push_back((now, ok));
+    }
+
+    /// the status and, for a 200, the body
+    async fn fetch(&self, req: Request<Body>) -> Result<(StatusCode, Option<Bytes>), hyper::Error> {
+        let resp = self.client.request(req).await?;
+        let status_code = resp.status();
+        if status_code == StatusCode::OK {
+            Ok((status_code, Some(body::to_bytes(resp.into_body()).await?)))
+        } else {
+            Ok((status_code, None))
+        }
+    }
+
+    /// GETs `url` within `timeout`, body included, and records the latency and status under `provider` and `endpoint`; `Ok(None)` on a 204 or 404
+    async fn get_json<T: DeserializeOwned>(&self, provider: &str, endpoint: &str, url: String) -> Result<Option<T>, JsonRequesterError> {
+        let req = Request::builder()
+            .uri(url)
+            .method(Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let start = Instant::now();
+        let fetched = tokio::time::timeout(self.timeout, self.fetch(req)).await;
+        let status = match &fetched {
+            Ok(Ok((status_code, _))) => status_code.as_u16().to_string(),
+            Ok(Err(_)) => "error".to_string(),
+            Err(_) => "timeout".to_string(),
+        };
+        self.metrics.observe_upstream(provider, endpoint, status.as_str(), start.elapsed());
+        // a 404 for an unknown profile is a working upstream too
+        let ok = matches!(&fetched, Ok(Ok((s, _))) if !s.is_server_error() && *s != StatusCode::TOO_MANY_REQUESTS);
+        self.record_outcome(ok);
+        let (status_code, data) = fetched.map_err(|_| JsonRequesterError::Timeout)??;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None if status_code == StatusCode::NO_CONTENT || status_code == StatusCode::NOT_FOUND => Ok(None),
+            None => Err(JsonRequesterError::StatusCode(status_code)),
+        }
+    }
+}
+
+
 #[derive(Clone)]
 pub struct MojangAPIRequester {
-    client: Arc<dyn GeneralClient + Send + Sync>
+    sender: Arc<HttpSender>,
+    /// `providers` of the config in order, or just `upstream`
+    providers: Arc<[Box<dyn ProfileProvider>]>,
 }
 
 impl MojangAPIRequester {
     
-    pub fn new(config: &ClientConfig) -> Self {
+    pub fn new(config: &ClientConfig, metrics: Metrics) -> Self {
+        let default_upstream = UpstreamConfig::default();
         let mut builder = Client::builder();
         builder.pool_idle_timeout(config.timeout);
         builder.pool_max_idle_per_host(config.pool_size);
@@ -96,31 +201,86 @@ impl MojangAPIRequester {
             let inner = builder.build(connector);
             Arc::new(ClientWrapper {inner, user_agent}) as Arc<(dyn GeneralClient + Send + Sync + 'static)>
         };
+        let sender = Arc::new(HttpSender {
+            client,
+            timeout: config.request_timeout,
+            metrics,
+            outcomes: Mutex::new(VecDeque::with_capacity(OUTCOME_WINDOW)),
+        });
+        let mut providers = config.providers.iter()
+            .filter_map(|provider| build_provider(provider, &sender))
+            .collect::<Vec<_>>();
+        if providers.is_empty() {
+            let session_server = base_url(config.upstream.session_server.as_str(), default_upstream.session_server.as_str());
+            let api_server = base_url(config.upstream.api_server.as_str(), default_upstream.api_server.as_str());
+            providers.push(Box::new(YggdrasilProvider::new("mojang".to_string(), &session_server, &api_server, sender.clone())));
+        }
         Self {
-            client
+            sender,
+            providers: providers.into(),
         } 
     }
 
-    pub async fn request_profile(&self, uuid: &Uuid) -> Result<Profile, JsonRequesterError> {
-        let req = Request::builder()
-            .uri(format!("https://sessionserver.mojang.com/session/minecraft/profile/{}", uuid))
-            .method(Method::GET)
-            .body(Body::empty())
-            .unwrap();
-        let resp = self.client.request(req).await?;
-        let status_code = resp.status();
-        if status_code == StatusCode::OK {
-            let data = body::aggregate(resp.into_body()).await?;
-            let profile = serde_json::from_reader(data.reader())?;
-            Ok(profile)
-        } else {
-            Err(JsonRequesterError::StatusCode(status_code))
+    /// the share of successful upstream requests among the recent ones and their count; `None` without any
+    pub fn success_rate(&self) -> Option<(f64, usize)> {
+        let mut outcomes = self.sender.outcomes.lock().unwrap();
+        prune_outcomes(&mut outcomes, Instant::now());
+        if outcomes.is_empty() {
+            return None;
+        }
+        let succeeded = outcomes.iter().filter(|(_, ok)| *ok).count();
+        Some((succeeded as f64 / outcomes.len() as f64, outcomes.len()))
+    }
+
+    /// `Ok(None)` if there is no such profile
+    pub async fn request_profile(&self, uuid: &Uuid) -> Result<Option<Profile>, JsonRequesterError> {
+        fall_back(&self.providers, |provider| provider.request_profile(uuid)).await
+    }
+
+    /// look up the account currently holding `name`; `Ok(None)` if there is none
+    pub async fn request_uuid(&self, name: &str) -> Result<Option<ProfileId>, JsonRequesterError> {
+        fall_back(&self.providers, |provider| provider.request_uuid(name)).await
+    }
+}
+
+
+/// the first answer of `providers`, skipping those that fail in a way `should_fall_back`; the last error if all of them do
+async fn fall_back<'a, T, F>(providers: &'a [Box<dyn ProfileProvider>], request: F) -> Result<T, JsonRequesterError>
+where
+    F: Fn(&'a dyn ProfileProvider) -> BoxFuture<'a, Result<T, JsonRequesterError>>,
+{
+    let mut last_error = None;
+    for provider in providers {
+        match request(provider.as_ref()).await {
+            Err(e) if e.should_fall_back() => {
+                tracing::warn!("provider {} failed: {}", provider.name(), &e);
+                last_error = Some(e);
+            }
+            result => return result,
         }
-        
+    }
+    Err(last_error.expect("at least one provider"))
+}
+
+
+fn prune_outcomes(outcomes: &mut VecDeque<(Instant, bool)>, now: Instant) {
+    while outcomes.front().map(|(t, _)| now.saturating_duration_since(*t) > OUTCOME_MAX_AGE).unwrap_or(false) {
+        outcomes.pop_front();
     }
 }
 
 
+fn base_url(configured: &str, default: &str) -> Arc<str> {
+    let base = configured.trim_end_matches('/');
+    match base.parse::<Uri>() {
+        Ok(uri) if uri.scheme().is_some() && uri.host().is_some() => Arc::from(base),
+        _ => {
+            tracing::warn!("ignore invalid upstream url {:?}, use {}", configured, default);
+            Arc::from(default)
+        }
+    }
+}
+
 fn build_proxy(proxy_cfg: &ProxyConfig) -> Option<Proxy> {
     let url_str = format!("http://{}", proxy_cfg.address);
     match url_str.parse() {
@@ -139,3 +299,47 @@ fn build_proxy(proxy_cfg: &ProxyConfig) -> Option<Proxy> {
     
 }
 
+
+#[cfg(test)]
+mod test {
+
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    /// answers every request with `status`, or with `Ok(None)` without one
+    struct StubProvider(Option<StatusCode>);
+
+    impl ProfileProvider for StubProvider {
+
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn request_profile<'a>(&'a self, _uuid: &'a Uuid) -> BoxFuture<'a, Result<Option<Profile>, JsonRequesterError>> {
+            futures_util::future::ready(self.0.map(JsonRequesterError::StatusCode).map_or(Ok(None), Err)).boxed()
+        }
+
+        fn request_uuid<'a>(&'a self, _name: &'a str) -> BoxFuture<'a, Result<Option<ProfileId>, JsonRequesterError>> {
+            futures_util::future::ready(self.0.map(JsonRequesterError::StatusCode).map_or(Ok(None), Err)).boxed()
+        }
+    }
+
+    fn request(statuses: &[Option<StatusCode>]) -> Result<Option<ProfileId>, JsonRequesterError> {
+        let providers = statuses.iter()
+            .map(|status| Box::new(StubProvider(*status)) as Box<dyn ProfileProvider>)
+            .collect::<Vec<_>>();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(fall_back(&providers, |provider| provider.request_uuid("name")))
+    }
+
+    #[test]
+    fn fall_back_order() {
+        assert!(matches!(request(&[Some(StatusCode::TOO_MANY_REQUESTS), Some(StatusCode::BAD_GATEWAY), None]), Ok(None)));
+        assert!(matches!(request(&[Some(StatusCode::BAD_REQUEST), None]), Err(JsonRequesterError::StatusCode(StatusCode::BAD_REQUEST))));
+        assert!(matches!(request(&[Some(StatusCode::TOO_MANY_REQUESTS), Some(StatusCode::SERVICE_UNAVAILABLE)]), Err(JsonRequesterError::StatusCode(StatusCode::SERVICE_UNAVAILABLE))));
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use serde::de;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::HttpSender;
+use super::JsonRequesterError;
+use super::config::ProviderConfig;
+use super::data::Profile;
+use super::data::ProfileId;
+
+/// a source of profiles; `MojangAPIRequester` asks them in order until one answers
+pub trait ProfileProvider: Send + Sync {
+
+    /// the `provider` of logs and metrics
+    fn name(&self) -> &str;
+
+    /// `Ok(None)` if there is no such profile
+    fn request_profile<'a>(&'a self, uuid: &'a Uuid) -> BoxFuture<'a, Result<Option<Profile>, JsonRequesterError>>;
+
+    /// look up the account currently holding `name`; `Ok(None)` if there is none
+    fn request_uuid<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Option<ProfileId>, JsonRequesterError>>;
+}
+
+
+/// the Mojang API, or a Yggdrasil server serving the same layout
+pub struct YggdrasilProvider {
+    name: String,
+    /// without trailing slashes
+    session_server: String,
+    api_server: String,
+    sender: Arc<HttpSender>,
+}
+
+impl YggdrasilProvider {
+
+    pub(super) fn new(name: String, session_server: &str, api_server: &str, sender: Arc<HttpSender>) -> Self {
+        Self {
+            name,
+            session_server: session_server.trim_end_matches('/').to_string(),
+            api_server: api_server.trim_end_matches('/').to_string(),
+            sender,
+        }
+    }
+}
+
+impl ProfileProvider for YggdrasilProvider {
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn request_profile<'a>(&'a self, uuid: &'a Uuid) -> BoxFuture<'a, Result<Option<Profile>, JsonRequesterError>> {
+        let url = format!("{}/session/minecraft/profile/{}", self.session_server, uuid);
+        self.sender.get_json(self.name.as_str(), "profile", url).boxed()
+    }
+
+    fn request_uuid<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Option<ProfileId>, JsonRequesterError>> {
+        let url = format!("{}/users/profiles/minecraft/{}", self.api_server, name);
+        self.sender.get_json(self.name.as_str(), "uuid", url).boxed()
+    }
+}
+
+
+/// a community mirror answering with Mojang shaped profiles, possibly wrapped in an envelope
+pub struct JsonApiProvider {
+    name: String,
+    profile_url: String,
+    uuid_url: String,
+    pointer: Option<String>,
+    sender: Arc<HttpSender>,
+}
+
+impl JsonApiProvider {
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str, url: String) -> Result<Option<T>, JsonRequesterError> {
+        let value: Option<Value> = self.sender.get_json(self.name.as_str(), endpoint, url).await?;
+        unwrap_envelope(value, self.pointer.as_deref())
+    }
+}
+
+/// an envelope without `pointer` is not an answer about the profile; only an explicit `null` (or a 204 / 404) means there is none
+fn unwrap_envelope<T: DeserializeOwned>(value: Option<Value>, pointer: Option<&str>) -> Result<Option<T>, JsonRequesterError> {
+    let value = match (value, pointer) {
+        (Some(value), Some(pointer)) => match value.pointer(pointer) {
+            Some(value) => Some(value.clone()),
+            None => return Err(JsonRequesterError::Deserialize(de::Error::custom(format!("no {} in the response", pointer)))),
+        },
+        (value, _) => value,
+    };
+    match value {
+        Some(Value::Null) | None => Ok(None),
+        Some(value) => Ok(Some(serde_json::from_value(value)?)),
+    }
+}
+
+impl ProfileProvider for JsonApiProvider {
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn request_profile<'a>(&'a self, uuid: &'a Uuid) -> BoxFuture<'a, Result<Option<Profile>, JsonRequesterError>> {
+        let url = self.profile_url
+            .replace("{uuid}", uuid.hyphenated().to_string().as_str())
+            .replace("{id}", uuid.simple().to_string().as_str());
+        self.get("profile", url).boxed()
+    }
+
+    fn request_uuid<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Option<ProfileId>, JsonRequesterError>> {
+        let url = self.uuid_url.replace("{name}", name);
+        self.get("uuid", url).boxed()
+    }
+}
+
+
+/// `None` (with a warning) for a provider whose urls do not parse
+pub(super) fn build_provider(config: &ProviderConfig, sender: &Arc<HttpSender>) -> Option<Box<dyn ProfileProvider>> {
+    match config {
+        ProviderConfig::Yggdrasil { name, session_server, api_server } => {
+            if !is_valid_url(session_server) || !is_valid_url(api_server) {
+                tracing::warn!("ignore provider {}: invalid url", name);
+                return None;
+            }
+            Some(Box::new(YggdrasilProvider::new(name.clone(), session_server, api_server, sender.clone())))
+        }
+        ProviderConfig::Json { name, profile_url, uuid_url, pointer } => {
+            if !is_valid_url(profile_url) || !is_valid_url(uuid_url) {
+                tracing::warn!("ignore provider {}: invalid url", name);
+                return None;
+            }
+            Some(Box::new(JsonApiProvider {
+                name: name.clone(),
+                profile_url: profile_url.clone(),
+                uuid_url: uuid_url.clone(),
+                pointer: pointer.clone(),
+                sender: sender.clone(),
+            }))
+        }
+    }
+}
+
+/// an absolute url once the placeholders are filled in
+fn is_valid_url(url: &str) -> bool {
+    let url = url.replace("{uuid}", "u").replace("{id}", "u").replace("{name}", "n");
+    match url.parse::<hyper::Uri>() {
+        Ok(uri) => uri.scheme().is_some() && uri.host().is_some(),
+        Err(_) => false,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn envelope() {
+        let player = json!({ "data": { "player": { "id": "069a79f444e94726a5befca90e38aaf5", "name": "Notch" } } });
+        let found = unwrap_envelope::<ProfileId>(Some(player), Some("/data/player"));
+        assert!(matches!(found, Ok(Some(ProfileId { ref name, .. })) if name == "Notch"));
+        assert!(matches!(unwrap_envelope::<ProfileId>(Some(json!({ "data": { "player": null } })), Some("/data/player")), Ok(None)));
+        assert!(matches!(unwrap_envelope::<ProfileId>(None, Some("/data/player")), Ok(None)));
+        // a changed envelope must not read as a missing profile
+        let moved = unwrap_envelope::<ProfileId>(Some(json!({ "player": {} })), Some("/data/player"));
+        assert!(matches!(moved, Err(JsonRequesterError::Deserialize(_))));
+    }
+}
@@ -7,6 +7,7 @@ use serde::Deserialize;
 use crate::client::config::ClientConfig;
 use crate::server::config::ServerConfig;
 use crate::storage::config::DatabaseConfig;
+use crate::webhook::config::WebhookConfig;
 
 
 #[derive(Debug,Default,Serialize,Deserialize)]
@@ -14,6 +15,8 @@ pub struct Config {
     pub client: ClientConfig,
     pub server: ServerConfig,
     pub database: DatabaseConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
 }
 
 
@@ -4,7 +4,9 @@ mod storage;
 mod client;
 mod server;
 mod config;
+mod metrics;
 mod utils;
+mod webhook;
 
 fn main() {
 
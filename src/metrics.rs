@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use prometheus::Encoder;
+use prometheus::HistogramOpts;
+use prometheus::HistogramVec;
+use prometheus::IntCounter;
+use prometheus::IntCounterVec;
+use prometheus::IntGaugeVec;
+use prometheus::Opts;
+use prometheus::Registry;
+use prometheus::TextEncoder;
+
+const NAMESPACE: &'static str = "name_history";
+
+/// all metrics of the service, exposed at `/metrics`; clones share the same collectors
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    cache_lookups: IntCounterVec,
+    upstream_requests: IntCounterVec,
+    upstream_request_duration: HistogramVec,
+    db_connections: IntGaugeVec,
+    name_changes: IntCounter,
+}
+
+impl Metrics {
+
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route, method and status").namespace(NAMESPACE),
+            &["route", "method", "status"],
+        ).unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latencies by route, method and status").namespace(NAMESPACE),
+            &["route", "method", "status"],
+        ).unwrap();
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "stored histories served from the cache (hit) or checked upstream (miss)").namespace(NAMESPACE),
+            &["result"],
+        ).unwrap();
+        let upstream_requests = IntCounterVec::new(
+            Opts::new("upstream_requests_total", "upstream API requests by provider, endpoint and status").namespace(NAMESPACE),
+            &["provider", "endpoint", "status"],
+        ).unwrap();
+        let upstream_request_duration = HistogramVec::new(
+            HistogramOpts::new("upstream_request_duration_seconds", "upstream API request latencies by provider, endpoint and status").namespace(NAMESPACE),
+            &["provider", "endpoint", "status"],
+        ).unwrap();
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "SQLite pool connections by state").namespace(NAMESPACE),
+            &["state"],
+        ).unwrap();
+        let name_changes = IntCounter::with_opts(
+            Opts::new("name_changes_detected_total", "name changes detected from upstream profiles").namespace(NAMESPACE),
+        ).unwrap();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(cache_lookups.clone())).unwrap();
+        registry.register(Box::new(upstream_requests.clone())).unwrap();
+        registry.register(Box::new(upstream_request_duration.clone())).unwrap();
+        registry.register(Box::new(db_connections.clone())).unwrap();
+        registry.register(Box::new(name_changes.clone())).unwrap();
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            cache_lookups,
+            upstream_requests,
+            upstream_request_duration,
+            db_connections,
+            name_changes,
+        }
+    }
+
+    pub fn observe_http(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [route, method, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration.with_label_values(&labels).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_cache(&self, hit: bool) {
+        self.cache_lookups.with_label_values(&[if hit { "hit" } else { "miss" }]).inc();
+    }
+
+    /// `status` is the HTTP status code, `timeout`, or `error` if no response came back
+    pub fn observe_upstream(&self, provider: &str, endpoint: &str, status: &str, elapsed: Duration) {
+        let labels = [provider, endpoint, status];
+        self.upstream_requests.with_label_values(&labels).inc();
+        self.upstream_request_duration.with_label_values(&labels).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn set_db_connections(&self, active: usize, idle: usize) {
+        self.db_connections.with_label_values(&["active"]).set(active as i64);
+        self.db_connections.with_label_values(&["idle"]).set(idle as i64);
+    }
+
+    pub fn inc_name_changes(&self) {
+        self.name_changes.inc();
+    }
+
+    /// everything in the Prometheus text format
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let metrics = Metrics::new();
+        metrics.observe_http("name_history", "GET", 200, Duration::from_millis(5));
+        metrics.observe_cache(true);
+        metrics.observe_upstream("mojang", "profile", "503", Duration::from_millis(50));
+        let text = metrics.encode().unwrap();
+        assert!(text.contains("name_history_http_requests_total{method=\"GET\",route=\"name_history\",status=\"200\"} 1"));
+        assert!(text.contains("name_history_cache_lookups_total{result=\"hit\"} 1"));
+        assert!(text.contains("name_history_upstream_requests_total{endpoint=\"profile\",provider=\"mojang\",status=\"503\"} 1"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::storage::data::ApiKey;
+use crate::storage::data::NameRecord;
+use crate::storage::data::PERMISSION_ADMIN;
+use crate::storage::data::PERMISSION_NAMES;
+use crate::storage::data::Update;
+
+use super::Context;
+use super::error::ApiError;
+use super::apikey;
+use super::config::AdminConfig;
+use super::namehistory::UPDATE_BY_MANUAL;
+
+#[derive(Debug, Deserialize)]
+pub struct AdminNameBody {
+    pub name: String,
+    /// milliseconds since the unix epoch; absent for the initial name
+    #[serde(rename = "changedToAt")]
+    pub changed_to_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminApiKeyBody {
+    pub name: String,
+    /// any of `read`, `refresh` and `admin`
+    pub permissions: Vec<String>,
+    /// requests per UTC day; absent for no limit
+    #[serde(rename = "dailyQuota")]
+    pub daily_quota: Option<u32>,
+}
+
+/// the key itself is only shown once, on creation
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+impl AdminNameBody {
+
+    fn changed_to_at(&self) -> Option<SystemTime> {
+        self.changed_to_at.map(|t| SystemTime::UNIX_EPOCH + Duration::from_millis(t))
+    }
+}
+
+pub async fn handle_insert_name(uuid: Uuid, authorization: Option<String>, body: AdminNameBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_insert_name_inner(uuid, authorization, body, admin_config, context).await {
+        Ok(data) => Ok(warp::reply::with_status(warp::reply::json(&data), StatusCode::CREATED).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_insert_name_inner(uuid: Uuid, authorization: Option<String>, body: AdminNameBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<NameRecord, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let changed_to_at = body.changed_to_at();
+    let record = context.database.admin_insert_name(actor.as_str(), &uuid, body.name.as_str(), changed_to_at.as_ref(), UPDATE_BY_MANUAL).await?;
+    tracing::info!("admin {} inserted name #{} @{}", actor, record.index, &uuid);
+    Ok(record)
+}
+
+pub async fn handle_edit_name(index: i64, authorization: Option<String>, body: AdminNameBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_edit_name_inner(index, authorization, body, admin_config, context).await {
+        Ok(data) => Ok(reply_found(data)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_edit_name_inner(index: i64, authorization: Option<String>, body: AdminNameBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<Option<NameRecord>, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let changed_to_at = body.changed_to_at();
+    let record = context.database.admin_edit_name(actor.as_str(), index, body.name.as_str(), changed_to_at.as_ref(), UPDATE_BY_MANUAL).await?;
+    tracing::info!("admin {} edited name #{}", actor, index);
+    Ok(record)
+}
+
+pub async fn handle_delete_name(index: i64, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_delete_name_inner(index, authorization, admin_config, context).await {
+        Ok(data) => Ok(reply_found(data)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_delete_name_inner(index: i64, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Option<NameRecord>, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let record = context.database.admin_delete_name(actor.as_str(), index).await?;
+    tracing::info!("admin {} deleted name #{}", actor, index);
+    Ok(record)
+}
+
+pub async fn handle_reset_update(uuid: Uuid, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_reset_update_inner(uuid, authorization, admin_config, context).await {
+        Ok(data) => Ok(reply_found(data)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_reset_update_inner(uuid: Uuid, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Option<Update>, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let update = context.database.admin_reset_update(actor.as_str(), &uuid).await?;
+    tracing::info!("admin {} reset update @{}", actor, &uuid);
+    Ok(update)
+}
+
+pub async fn handle_create_api_key(authorization: Option<String>, body: AdminApiKeyBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_create_api_key_inner(authorization, body, admin_config, context).await {
+        Ok(data) => Ok(warp::reply::with_status(warp::reply::json(&data), StatusCode::CREATED).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_create_api_key_inner(authorization: Option<String>, body: AdminApiKeyBody, admin_config: Arc<AdminConfig>, context: Context) -> Result<CreatedApiKey, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let mut permissions = 0;
+    for name in body.permissions.iter() {
+        match PERMISSION_NAMES.iter().find(|(_, n)| n == name) {
+            Some((p, _)) => permissions |= p,
+            None => return Err(ApiError::InvalidRequest(format!("unknown permission: {}", name))),
+        }
+    }
+    let key = apikey::generate_key();
+    let api_key = context.database.admin_create_api_key(actor.as_str(), body.name.as_str(), apikey::hash_key(key.as_str()).as_str(), permissions, body.daily_quota).await?;
+    tracing::info!("admin {} created api key #{} {}", &actor, api_key.index, &api_key.name);
+    Ok(CreatedApiKey { key, api_key })
+}
+
+pub async fn handle_get_api_keys(authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_get_api_keys_inner(authorization, admin_config, context).await {
+        Ok(data) => Ok(warp::reply::json(&data).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_get_api_keys_inner(authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Vec<ApiKey>, ApiError> {
+    authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    Ok(context.database.get_api_keys().await?)
+}
+
+pub async fn handle_delete_api_key(index: i64, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_delete_api_key_inner(index, authorization, admin_config, context).await {
+        Ok(data) => Ok(reply_found(data)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_delete_api_key_inner(index: i64, authorization: Option<String>, admin_config: Arc<AdminConfig>, context: Context) -> Result<Option<ApiKey>, ApiError> {
+    let actor = authorize(authorization.as_deref(), admin_config.as_ref(), &context).await?;
+    let api_key = context.database.admin_delete_api_key(actor.as_str(), index).await?;
+    tracing::info!("admin {} deleted api key #{}", &actor, index);
+    Ok(api_key)
+}
+
+
+fn reply_found<T: Serialize>(data: Option<T>) -> Response<Body> {
+    match data {
+        Some(data) => warp::reply::json(&data).into_response(),
+        None => ApiError::NotFound.into_response(),
+    }
+}
+
+/// checks an `Authorization: Bearer <token>` header against the configured admin tokens, then against the api keys with the admin permission,
+/// and returns the name to record as the actor
+async fn authorize(authorization: Option<&str>, admin_config: &AdminConfig, context: &Context) -> Result<String, ApiError> {
+    let token = match authorization.and_then(|s| s.strip_prefix("Bearer ")) {
+        Some(token) => token,
+        None => return Err(ApiError::Unauthorized("missing or invalid admin token")),
+    };
+    if let Some(t) = admin_config.tokens.iter().find(|t| constant_time_eq(t.token.as_bytes(), token.as_bytes())) {
+        return Ok(t.name.clone());
+    }
+    match apikey::find_key(token, context).await? {
+        Some(api_key) if api_key.allows(PERMISSION_ADMIN) => Ok(format!("key:{}", api_key.name)),
+        Some(_) => Err(ApiError::Forbidden("api key lacks the admin permission")),
+        None => Err(ApiError::Unauthorized("missing or invalid admin token")),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
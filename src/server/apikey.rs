@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::Digest;
+use sha2::Sha256;
+use warp::Rejection;
+
+use crate::storage::data::ApiKey;
+use crate::storage::data::PERMISSION_ADMIN;
+use crate::storage::data::PERMISSION_READ;
+
+use super::Context;
+use super::error::ApiError;
+use super::ratelimit::ClientKey;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyQuery {
+    pub key: Option<String>,
+}
+
+/// who made the current request
+#[derive(Debug, Clone)]
+pub enum Caller {
+    Anonymous,
+    Key(Arc<ApiKey>),
+}
+
+impl Caller {
+
+    /// anonymous callers may do everything but admin; keys only what they were granted
+    pub fn allows(&self, permissions: u32) -> bool {
+        match self {
+            Self::Anonymous => permissions & PERMISSION_ADMIN == 0,
+            Self::Key(key) => key.allows(permissions),
+        }
+    }
+}
+
+
+/// resolves the key of an `Authorization: Bearer` header or a `?key=` parameter, checks the read budget of the caller and only then
+/// counts the request against the daily quota of the key, and sets `caller` and `client` on the context
+pub(crate) async fn resolve_caller(mut context: Context, authorization: Option<String>, query: ApiKeyQuery) -> Result<Context, Rejection> {
+    let key = authorization.as_deref().and_then(|s| s.strip_prefix("Bearer ")).or(query.key.as_deref());
+    let key_hash = match key {
+        Some(key) => {
+            let key_hash = hash_key(key);
+            let api_key = match context.database.get_api_key_by_hash(key_hash.as_str()).await {
+                Ok(Some(api_key)) if api_key.allows(PERMISSION_READ) => api_key,
+                Ok(Some(_)) => return Err(warp::reject::custom(ApiError::Forbidden("api key lacks the read permission"))),
+                Ok(None) => return Err(warp::reject::custom(ApiError::Unauthorized("invalid api key"))),
+                Err(e) => return Err(warp::reject::custom(ApiError::from(e))),
+            };
+            context.client = ClientKey::ApiKey(api_key.index);
+            Some(key_hash)
+        }
+        None if !context.api_key_config.allow_anonymous => {
+            return Err(warp::reject::custom(ApiError::Unauthorized("api key required")));
+        }
+        None => None,
+    };
+    // a request turned away by the rate limit does not use up the quota
+    if let Err(wait) = context.rate_limiter.check_read(&context.client) {
+        tracing::debug!("read limit hit by {}", &context.client);
+        return Err(warp::reject::custom(ApiError::RateLimited(wait)));
+    }
+    if let Some(key_hash) = key_hash {
+        let now = SystemTime::now();
+        let day = days_since_epoch(&now);
+        let api_key = match context.database.use_api_key(key_hash.as_str(), day, PERMISSION_READ).await {
+            Ok(Some(api_key)) => api_key,
+            Ok(None) => {
+                // the key was found above, so it is out of quota unless it was revoked in between
+                let e = match context.database.get_api_key_by_hash(key_hash.as_str()).await {
+                    Ok(None) => ApiError::Unauthorized("invalid api key"),
+                    Ok(Some(_)) => ApiError::QuotaExceeded(until_next_day(&now)),
+                    Err(e) => ApiError::from(e),
+                };
+                return Err(warp::reject::custom(e));
+            }
+            Err(e) => return Err(warp::reject::custom(ApiError::from(e))),
+        };
+        context.caller = Caller::Key(Arc::new(api_key));
+    }
+    Ok(context)
+}
+
+/// looks up a key without counting it against its quota
+pub(crate) async fn find_key(key: &str, context: &Context) -> Result<Option<ApiKey>, ApiError> {
+    Ok(context.database.get_api_key_by_hash(hash_key(key).as_str()).await?)
+}
+
+/// a new random key, 32 bytes in hex
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// keys are stored as the hex encoded sha256 only
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn days_since_epoch(now: &SystemTime) -> i64 {
+    let secs = now.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    (secs / SECONDS_PER_DAY) as i64
+}
+
+fn until_next_day(now: &SystemTime) -> Duration {
+    let secs = now.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    Duration::from_secs(SECONDS_PER_DAY - secs % SECONDS_PER_DAY)
+}
@@ -0,0 +1,46 @@
+use hyper::Response;
+use hyper::Body;
+use serde::Deserialize;
+use serde::Serialize;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::storage::data::NameRecord;
+
+use super::Context;
+use super::error::ApiError;
+
+pub const CHANGES_DEFAULT_LIMIT: u32 = 100;
+pub const CHANGES_MAX_LIMIT: u32 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// `next` of the previous page
+    pub since: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangesPage {
+
+    pub changes: Vec<NameRecord>,
+
+    /// cursor to pass as `since` next time; unchanged if there was nothing new
+    pub next: i64,
+}
+
+pub async fn handle_get_changes(query: ChangesQuery, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_get_changes_inner(query, context).await {
+        Ok(data) => Ok(warp::reply::json(&data).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+
+async fn handle_get_changes_inner(query: ChangesQuery, context: Context) -> Result<ChangesPage, ApiError> {
+    let since = query.since.unwrap_or(0);
+    let limit = query.limit.unwrap_or(CHANGES_DEFAULT_LIMIT).clamp(1, CHANGES_MAX_LIMIT);
+    let changes = context.database.get_name_changes(since, limit).await?;
+    let next = changes.last().map(|r| r.index).unwrap_or(since);
+    Ok(ChangesPage { changes, next })
+}
@@ -1,5 +1,10 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::Serialize;
 use serde::Deserialize;
@@ -8,6 +13,21 @@ use serde::Deserialize;
 pub struct ServerConfig {
     pub address: SocketAddr,
     pub static_files: Option<PathBuf>,
+    /// answer profiles upstream does not know with 204, like the legacy Mojang API, instead of 404
+    #[serde(default)]
+    pub missing_no_content: bool,
+    #[serde(default)]
+    pub bulk: BulkConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub refresh: RefreshConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub api_keys: ApiKeyConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
 }
 
 impl Default for ServerConfig {
@@ -15,7 +35,147 @@ impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             address: SocketAddr::from(([127, 0, 0, 1], 6080)),
-            static_files: None
+            static_files: None,
+            missing_no_content: false,
+            bulk: BulkConfig::default(),
+            admin: AdminConfig::default(),
+            refresh: RefreshConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            api_keys: ApiKeyConfig::default(),
+            health: HealthConfig::default(),
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct BulkConfig {
+    pub max_uuids: usize,
+    pub max_upstream_requests: usize,
+}
+
+impl Default for BulkConfig {
+
+    fn default() -> Self {
+        Self {
+            max_uuids: 1000,
+            max_upstream_requests: 16,
+        }
+    }
+}
+
+
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct AdminConfig {
+    pub tokens: Vec<AdminTokenConfig>,
+}
+
+#[derive(Clone,Serialize,Deserialize)]
+pub struct AdminTokenConfig {
+    /// recorded as the actor in the audit table
+    pub name: String,
+    pub token: String,
+}
+
+/// the config is printed on startup, the token must not be
+impl Debug for AdminTokenConfig {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminTokenConfig")
+            .field("name", &self.name)
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+
+/// limits on `?refresh=true` / `Cache-Control: no-cache`, which bypass the `use_cache` TTLs
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct RefreshConfig {
+    pub client_burst: u32,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub client_period: Duration,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub uuid_period: Duration,
+}
+
+impl Default for RefreshConfig {
+
+    fn default() -> Self {
+        Self {
+            client_burst: 5,
+            client_period: Duration::from_secs(60),
+            uuid_period: Duration::from_secs(60),
+        }
+    }
+}
+
+
+/// token buckets per client: `read` is spent by every request, `upstream` only by requests that have to ask Mojang
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub read_burst: u32,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub read_period: Duration,
+    pub upstream_burst: u32,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub upstream_period: Duration,
+    /// reverse proxies whose `Forwarded` / `X-Forwarded-For` headers name the client; any other peer is the client itself
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read_burst: 120,
+            read_period: Duration::from_millis(250),
+            upstream_burst: 20,
+            upstream_period: Duration::from_secs(3),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct ApiKeyConfig {
+    /// serve requests without a key, with read and refresh permissions
+    pub allow_anonymous: bool,
+}
+
+impl Default for ApiKeyConfig {
+
+    fn default() -> Self {
+        Self {
+            allow_anonymous: true,
+        }
+    }
+}
+
+
+/// checks of `/readyz`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    #[serde(with="crate::utils::duration_fmt")]
+    pub database_timeout: Duration,
+    /// not ready below this share of successful recent upstream requests; 0 to only report it
+    pub min_upstream_success_rate: f64,
+}
+
+impl Default for HealthConfig {
+
+    fn default() -> Self {
+        Self {
+            database_timeout: Duration::from_secs(2),
+            min_upstream_success_rate: 0.0,
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::time::Duration;
+
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use hyper::header;
+use serde::Serialize;
+use utoipa::ToSchema;
+use warp::Rejection;
+use warp::Reply;
+use warp::reject::Reject;
+
+use crate::client::JsonRequesterError;
+use crate::storage::data::Tombstone;
+
+pub const PROBLEM_CONTENT_TYPE: &'static str = "application/problem+json";
+
+/// every way a request can fail; each maps to a stable `code`, an HTTP status and an RFC 7807 body
+/// the underlying errors are kept as their message, so one error can be handed to every caller of a shared upstream request
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    Database(String),
+    /// upstream answered with an unexpected status
+    UpstreamStatus(StatusCode),
+    /// upstream could not be reached
+    UpstreamTransport(String),
+    /// upstream answered with something that is not a profile
+    UpstreamDecode(String),
+    /// no upstream answered within `ClientConfig::request_timeout`
+    UpstreamTimeout,
+    InvalidUuid(String),
+    InvalidRequest(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType,
+    NotFound,
+    MethodNotAllowed,
+    /// `legacy` answers with an empty 204 instead, see `ServerConfig::missing_no_content`
+    ProfileNotFound { tombstone: Option<Tombstone>, legacy: bool },
+    Unauthorized(&'static str),
+    Forbidden(&'static str),
+    RateLimited(Duration),
+    RefreshLimited(Duration),
+    QuotaExceeded(Duration),
+    Internal(String),
+}
+
+impl ApiError {
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "database_error",
+            Self::UpstreamStatus(_) => "upstream_status",
+            Self::UpstreamTransport(_) => "upstream_unavailable",
+            Self::UpstreamDecode(_) => "upstream_decode",
+            Self::UpstreamTimeout => "upstream_timeout",
+            Self::InvalidUuid(_) => "invalid_uuid",
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::PayloadTooLarge(_) => "payload_too_large",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::NotFound => "not_found",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::ProfileNotFound { .. } => "profile_not_found",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::RateLimited(_) => "rate_limited",
+            Self::RefreshLimited(_) => "refresh_limited",
+            Self::QuotaExceeded(_) => "quota_exceeded",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Database(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UpstreamStatus(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UpstreamTransport(_) | Self::UpstreamDecode(_) => StatusCode::BAD_GATEWAY,
+            Self::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::InvalidUuid(_) | Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::NotFound | Self::ProfileNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::RateLimited(_) | Self::RefreshLimited(_) | Self::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "Database error",
+            Self::UpstreamStatus(_) => "Upstream returned an error",
+            Self::UpstreamTransport(_) => "Upstream unavailable",
+            Self::UpstreamDecode(_) => "Invalid upstream response",
+            Self::UpstreamTimeout => "Upstream timed out",
+            Self::InvalidUuid(_) => "Invalid UUID",
+            Self::InvalidRequest(_) => "Invalid request",
+            Self::PayloadTooLarge(_) => "Payload too large",
+            Self::UnsupportedMediaType => "Unsupported media type",
+            Self::NotFound => "Not found",
+            Self::MethodNotAllowed => "Method not allowed",
+            Self::ProfileNotFound { .. } => "No such profile",
+            Self::Unauthorized(_) => "Unauthorized",
+            Self::Forbidden(_) => "Forbidden",
+            Self::RateLimited(_) => "Rate limited",
+            Self::RefreshLimited(_) => "Too many forced refreshes",
+            Self::QuotaExceeded(_) => "Daily quota exceeded",
+            Self::Internal(_) => "Internal error",
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Self::UpstreamStatus(s) => Some(format!("upstream status {}", s.as_u16())),
+            Self::InvalidUuid(s) => Some(format!("not a uuid: {}", s)),
+            Self::Database(s) | Self::UpstreamTransport(s) | Self::UpstreamDecode(s) => Some(s.clone()),
+            Self::InvalidRequest(s) | Self::PayloadTooLarge(s) | Self::Internal(s) => Some(s.clone()),
+            Self::Unauthorized(s) | Self::Forbidden(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited(wait) | Self::RefreshLimited(wait) | Self::QuotaExceeded(wait) => Some(*wait),
+            _ => None,
+        }
+    }
+
+    fn log(&self) {
+        match self {
+            Self::Database(e) => tracing::error!("database error {}", e),
+            Self::UpstreamStatus(s) => tracing::warn!("request failed: {}", s),
+            Self::UpstreamTransport(e) => tracing::error!("request error: {}", e),
+            Self::UpstreamDecode(e) => tracing::error!("request error: {}", e),
+            Self::UpstreamTimeout => tracing::warn!("request failed: timeout"),
+            Self::Internal(s) => tracing::error!("internal error: {}", s),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{}: {}", self.code(), detail),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e.to_string())
+    }
+}
+
+impl From<&JsonRequesterError> for ApiError {
+
+    fn from(e: &JsonRequesterError) -> Self {
+        match e {
+            JsonRequesterError::StatusCode(s) => Self::UpstreamStatus(*s),
+            JsonRequesterError::Hyper(e) => Self::UpstreamTransport(e.to_string()),
+            JsonRequesterError::Deserialize(e) => Self::UpstreamDecode(e.to_string()),
+            JsonRequesterError::Timeout => Self::UpstreamTimeout,
+        }
+    }
+}
+
+impl From<JsonRequesterError> for ApiError {
+
+    fn from(e: JsonRequesterError) -> Self {
+        Self::from(&e)
+    }
+}
+
+impl Reject for ApiError {}
+
+
+/// the body of every error response, an RFC 7807 problem
+#[derive(Serialize, ToSchema)]
+pub struct Problem<'a> {
+    /// `/problems/<code>`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: &'a str,
+    /// the HTTP status
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// stable across versions, one of `database_error`, `upstream_status`, `upstream_unavailable`, `upstream_decode`, `upstream_timeout`,
+    /// `invalid_uuid`, `invalid_request`, `payload_too_large`, `unsupported_media_type`, `not_found`, `method_not_allowed`,
+    /// `profile_not_found`, `unauthorized`, `forbidden`, `rate_limited`, `refresh_limited`, `quota_exceeded` and `internal_error`
+    pub code: &'a str,
+    /// seconds to wait before retrying, same as the `Retry-After` header
+    #[serde(rename = "retryAfter", skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    /// with `profile_not_found`, if the profile existed before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Tombstone>)]
+    pub tombstone: Option<&'a Tombstone>,
+}
+
+impl ApiError {
+
+    /// logs the error and renders it; rejections only lend out their error, so this works on a reference
+    pub fn to_response(&self) -> Response<Body> {
+        self.log();
+        if let Self::ProfileNotFound { legacy: true, .. } = self {
+            return StatusCode::NO_CONTENT.into_response();
+        }
+        let status = self.status();
+        // whole seconds, rounded up
+        let retry_after = self.retry_after().map(|wait| wait.as_secs() + 1);
+        let problem = Problem {
+            kind: format!("/problems/{}", self.code()),
+            title: self.title(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            retry_after,
+            tombstone: match self {
+                Self::ProfileNotFound { tombstone, .. } => tombstone.as_ref(),
+                _ => None,
+            },
+        };
+        let mut resp = match serde_json::to_vec(&problem) {
+            Ok(body) => Response::new(Body::from(body)),
+            Err(e) => {
+                tracing::error!("serialize error {}", &e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        *resp.status_mut() = status;
+        resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        if let Some(retry_after) = retry_after {
+            resp.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from(retry_after));
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            resp.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+        resp
+    }
+}
+
+impl Reply for ApiError {
+
+    fn into_response(self) -> Response<Body> {
+        self.to_response()
+    }
+}
+
+
+/// turns every rejection, ours and warp's own, into a problem response
+pub async fn recover(rejection: Rejection) -> Result<Response<Body>, Infallible> {
+    let e = if let Some(e) = rejection.find::<ApiError>() {
+        return Ok(e.to_response());
+    } else if rejection.is_not_found() {
+        ApiError::NotFound
+    } else if let Some(e) = rejection.find::<warp::filters::body::BodyDeserializeError>() {
+        ApiError::InvalidRequest(e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::InvalidQuery>() {
+        ApiError::InvalidRequest(e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::InvalidHeader>() {
+        ApiError::InvalidRequest(e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::MissingHeader>() {
+        ApiError::InvalidRequest(e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::PayloadTooLarge>() {
+        ApiError::PayloadTooLarge(e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::LengthRequired>() {
+        ApiError::InvalidRequest(e.to_string())
+    } else if rejection.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        ApiError::UnsupportedMediaType
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        ApiError::MethodNotAllowed
+    } else {
+        ApiError::Internal(format!("unhandled rejection: {:?}", rejection))
+    };
+    Ok(e.into_response())
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn problem_response() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let resp = ApiError::RateLimited(Duration::from_millis(1500)).into_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "2");
+        let body = rt.block_on(hyper::body::to_bytes(resp.into_body())).unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["type"], "/problems/rate_limited");
+        assert_eq!(problem["code"], "rate_limited");
+        assert_eq!(problem["status"], 429);
+        assert_eq!(problem["retryAfter"], 2);
+
+        let e = ApiError::from(JsonRequesterError::StatusCode(StatusCode::BAD_GATEWAY));
+        assert_eq!((e.code(), e.status()), ("upstream_status", StatusCode::SERVICE_UNAVAILABLE));
+        let resp = ApiError::ProfileNotFound { tombstone: None, legacy: true }.into_response();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,135 @@
+use std::convert::Infallible;
+
+use futures_util::SinkExt;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use uuid::Uuid;
+use uuid::fmt::Simple;
+use warp::Reply;
+use warp::sse;
+use warp::ws::Message;
+use warp::ws::WebSocket;
+use warp::ws::Ws;
+
+use super::Context;
+
+pub const EVENTS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameChangeEvent {
+
+    pub id: Simple,
+
+    #[serde(rename = "oldName")]
+    pub old_name: String,
+
+    #[serde(rename = "newName")]
+    pub new_name: String,
+
+    #[serde(rename = "changedToAt")]
+    pub changed_to_at: u128,
+
+    pub source: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    pub uuid: Option<Uuid>,
+}
+
+impl EventsQuery {
+
+    fn matches(&self, event: &NameChangeEvent) -> bool {
+        self.uuid.as_ref().map(|uuid| uuid == event.id.as_uuid()).unwrap_or(true)
+    }
+}
+
+
+/// in-process fan-out of detected name changes; subscribers are dropped once `shutdown` fires
+/// so that they do not hold the graceful shutdown open
+#[derive(Clone)]
+pub struct EventChannel {
+    sender: broadcast::Sender<NameChangeEvent>,
+    shutdown: watch::Receiver<()>,
+}
+
+impl EventChannel {
+
+    pub fn new(shutdown: watch::Receiver<()>) -> Self {
+        let (sender, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self { sender, shutdown }
+    }
+
+    pub fn publish(&self, event: NameChangeEvent) {
+        // an error only means that nobody is listening
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NameChangeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+
+pub fn handle_events_sse(query: EventsQuery, context: Context) -> impl Reply {
+    let mut shutdown = context.events.shutdown.clone();
+    let stream = BroadcastStream::new(context.events.subscribe())
+        .filter_map(move |r| {
+            let event = match r {
+                Ok(event) if query.matches(&event) => sse::Event::default().event("namechange").json_data(&event).ok(),
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(n)) => {
+                    tracing::warn!("event stream lagged, {} events skipped", n);
+                    None
+                }
+            };
+            async move { event.map(Ok::<_, Infallible>) }
+        })
+        .take_until(async move { let _ = shutdown.changed().await; });
+    sse::reply(sse::keep_alive().stream(stream))
+}
+
+pub fn handle_events_ws(ws: Ws, query: EventsQuery, context: Context) -> impl Reply {
+    ws.on_upgrade(move |socket| forward_events(socket, query, context))
+}
+
+async fn forward_events(socket: WebSocket, query: EventsQuery, context: Context) {
+    let mut receiver = context.events.subscribe();
+    let mut shutdown = context.events.shutdown.clone();
+    let (mut outgoing, mut incoming) = socket.split();
+    loop {
+        tokio::select! {
+            r = receiver.recv() => match r {
+                Ok(event) if query.matches(&event) => {
+                    let text = match serde_json::to_string(&event) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            tracing::error!("event serialize error: {}", e);
+                            continue;
+                        }
+                    };
+                    if outgoing.send(Message::text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!("event socket lagged, {} events skipped", n);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            m = incoming.next() => match m {
+                Some(Ok(m)) if !m.is_close() => {}
+                _ => break,
+            },
+            _ = shutdown.changed() => break,
+        }
+    }
+    let _ = outgoing.close().await;
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use hyper::HeaderMap;
+use hyper::Response;
+use hyper::Body;
+use hyper::header;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use warp::Reply;
+
+use crate::storage::data::NameHistory;
+
+use super::error::ApiError;
+use super::v2::NameHistoryV2;
+
+/// the representations of the history endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+    Msgpack,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    /// overrides `Accept`
+    pub format: Option<Format>,
+}
+
+impl Format {
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Msgpack => "application/msgpack",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "text/csv" => Some(Self::Csv),
+            "application/x-ndjson" | "application/ndjson" => Some(Self::Ndjson),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::Msgpack),
+            _ => None,
+        }
+    }
+
+    /// `format` if given, else the supported media type of `Accept` with the highest quality; json for anything else
+    pub fn negotiate(format: Option<Format>, headers: &HeaderMap) -> Self {
+        if let Some(format) = format {
+            return format;
+        }
+        let accept = match headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) {
+            Some(accept) => accept,
+            None => return Self::Json,
+        };
+        let mut best: Option<(Self, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if let Some(format) = Self::from_media_type(media_type.as_str()) {
+                // the first of equally preferred types wins
+                if quality > 0.0 && best.map(|(_, q)| quality > q).unwrap_or(true) {
+                    best = Some((format, quality));
+                }
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or(Self::Json)
+    }
+}
+
+
+/// a body the history endpoints serve; the line based formats write it as a list of flat records
+pub trait Records: Serialize {
+
+    /// the CSV header, and the fields of each record written to it
+    const COLUMNS: &'static [&'static str];
+
+    /// one NDJSON line each, its fields written in `COLUMNS` order
+    fn records(&self) -> Result<Vec<Value>, serde_json::Error>;
+}
+
+impl Records for NameHistory {
+
+    const COLUMNS: &'static [&'static str] = &["name", "changedToAt"];
+
+    fn records(&self) -> Result<Vec<Value>, serde_json::Error> {
+        self.iter().map(serde_json::to_value).collect()
+    }
+}
+
+impl Records for HashMap<Uuid, NameHistory> {
+
+    const COLUMNS: &'static [&'static str] = &["id", "name", "changedToAt"];
+
+    fn records(&self) -> Result<Vec<Value>, serde_json::Error> {
+        let mut uuids = self.keys().collect::<Vec<_>>();
+        uuids.sort();
+        let mut records = Vec::new();
+        for uuid in uuids {
+            for element in self[uuid].iter() {
+                let mut record = serde_json::Map::new();
+                record.insert("id".to_string(), Value::String(uuid.to_string()));
+                if let Value::Object(fields) = serde_json::to_value(element)? {
+                    record.extend(fields);
+                }
+                records.push(Value::Object(record));
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// the cache metadata is left out of the line based formats; it is still in the `Cache-Control` and `X-Cache` headers
+impl Records for NameHistoryV2 {
+
+    const COLUMNS: &'static [&'static str] = &["index", "name", "changedToAt", "changedToAtIso", "source", "exact"];
+
+    fn records(&self) -> Result<Vec<Value>, serde_json::Error> {
+        self.names.iter().map(serde_json::to_value).collect()
+    }
+}
+
+
+pub fn encode<T: Records>(data: &T, format: Format) -> Result<Vec<u8>, ApiError> {
+    let serialize_error = |e: &dyn std::fmt::Display| ApiError::Internal(format!("serialize error {}", e));
+    match format {
+        Format::Json => serde_json::to_vec(data).map_err(|e| serialize_error(&e)),
+        Format::Ndjson => {
+            let mut body = Vec::new();
+            for record in data.records().map_err(|e| serialize_error(&e))? {
+                write_ndjson_line(&mut body, T::COLUMNS, &record).map_err(|e| serialize_error(&e))?;
+            }
+            Ok(body)
+        }
+        Format::Csv => {
+            let mut body = String::new();
+            write_csv_row(&mut body, T::COLUMNS.iter().map(|c| c.to_string()));
+            for record in data.records().map_err(|e| serialize_error(&e))? {
+                write_csv_row(&mut body, T::COLUMNS.iter().map(|c| csv_field(record.get(c))));
+            }
+            Ok(body.into_bytes())
+        }
+        // through a json value, so the fields skipped by the hand written `Serialize` impls and the u128 timestamps come out as in json
+        Format::Msgpack => {
+            let value = serde_json::to_value(data).map_err(|e| serialize_error(&e))?;
+            rmp_serde::to_vec_named(&value).map_err(|e| serialize_error(&e))
+        }
+    }
+}
+
+/// `data` in `format`, with its content type
+pub fn reply<T: Records>(data: &T, format: Format) -> Response<Body> {
+    match encode(data, format) {
+        Ok(body) => {
+            let mut resp = Response::new(Body::from(body));
+            resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(format.content_type()));
+            resp.headers_mut().insert(header::VARY, header::HeaderValue::from_static("accept"));
+            resp
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// the fields of `record` in `columns` order, leaving out the absent ones
+fn write_ndjson_line(out: &mut Vec<u8>, columns: &[&str], record: &Value) -> Result<(), serde_json::Error> {
+    out.push(b'{');
+    let mut first = true;
+    for column in columns {
+        if let Some(value) = record.get(column) {
+            if !first {
+                out.push(b',');
+            }
+            first = false;
+            serde_json::to_writer(&mut *out, column)?;
+            out.push(b':');
+            serde_json::to_writer(&mut *out, value)?;
+        }
+    }
+    out.extend_from_slice(b"}\n");
+    Ok(())
+}
+
+fn csv_field(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    }
+}
+
+/// RFC 4180: fields with a separator, a quote or a line break are quoted, quotes doubled
+fn write_csv_row(out: &mut String, fields: impl Iterator<Item = String>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(field.replace('"', "\"\"").as_str());
+            out.push('"');
+        } else {
+            out.push_str(field.as_str());
+        }
+    }
+    out.push_str("\r\n");
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use crate::storage::data::NameHistoryElement;
+
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Format::negotiate(None, &headers), Format::Json);
+        headers.insert(header::ACCEPT, "text/html, text/csv;q=0.5, application/msgpack;q=0.9".parse().unwrap());
+        assert_eq!(Format::negotiate(None, &headers), Format::Msgpack);
+        assert_eq!(Format::negotiate(Some(Format::Ndjson), &headers), Format::Ndjson);
+        headers.insert(header::ACCEPT, "text/html".parse().unwrap());
+        assert_eq!(Format::negotiate(None, &headers), Format::Json);
+    }
+
+    #[test]
+    fn encode_history() {
+        let history = vec![
+            NameHistoryElement::new_initial("a,\"b\"".to_string()),
+            NameHistoryElement::new("c".to_string(), SystemTime::UNIX_EPOCH + Duration::from_millis(1000)),
+        ];
+        let csv = String::from_utf8(encode(&history, Format::Csv).unwrap()).unwrap();
+        assert_eq!(csv, "name,changedToAt\r\n\"a,\"\"b\"\"\",\r\nc,1000\r\n");
+        let ndjson = String::from_utf8(encode(&history, Format::Ndjson).unwrap()).unwrap();
+        assert_eq!(ndjson, "{\"name\":\"a,\\\"b\\\"\"}\n{\"name\":\"c\",\"changedToAt\":1000}\n");
+        let msgpack = encode(&history, Format::Msgpack).unwrap();
+        let value: Value = rmp_serde::from_slice(msgpack.as_slice()).unwrap();
+        assert_eq!(value, serde_json::to_value(&history).unwrap());
+    }
+}
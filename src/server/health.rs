@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use serde::Serialize;
+use warp::Rejection;
+use warp::Reply;
+
+use super::Context;
+use super::config::HealthConfig;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseCheck {
+    pub status: CheckStatus,
+    #[serde(rename = "latencyMs", skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpstreamCheck {
+    pub status: CheckStatus,
+    /// `None` while there were no recent upstream requests
+    #[serde(rename = "successRate")]
+    pub success_rate: Option<f64>,
+    pub samples: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadyChecks {
+    pub database: DatabaseCheck,
+    pub upstream: UpstreamCheck,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: CheckStatus,
+    pub checks: ReadyChecks,
+}
+
+pub fn handle_get_healthz() -> Response<Body> {
+    warp::reply::json(&serde_json::json!({ "status": "ok" })).into_response()
+}
+
+/// 200 if every check passes, 503 otherwise; the body has the result of each check either way
+pub async fn handle_get_readyz(health_config: Arc<HealthConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    let database = check_database(health_config.as_ref(), &context).await;
+    let upstream = check_upstream(health_config.as_ref(), &context);
+    let ready = matches!(database.status, CheckStatus::Ok) && matches!(upstream.status, CheckStatus::Ok);
+    let data = Readiness {
+        status: if ready { CheckStatus::Ok } else { CheckStatus::Fail },
+        checks: ReadyChecks { database, upstream },
+    };
+    let status_code = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok(warp::reply::with_status(warp::reply::json(&data), status_code).into_response())
+}
+
+async fn check_database(health_config: &HealthConfig, context: &Context) -> DatabaseCheck {
+    let start = Instant::now();
+    let error = match tokio::time::timeout(health_config.database_timeout, context.database.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some("timeout".to_string()),
+    };
+    if let Some(error) = error {
+        tracing::warn!("readiness database check failed: {}", &error);
+        DatabaseCheck { status: CheckStatus::Fail, latency_ms: None, error: Some(error) }
+    } else {
+        DatabaseCheck { status: CheckStatus::Ok, latency_ms: Some(start.elapsed().as_millis()), error: None }
+    }
+}
+
+fn check_upstream(health_config: &HealthConfig, context: &Context) -> UpstreamCheck {
+    match context.requester.success_rate() {
+        Some((rate, samples)) => {
+            let status = if rate >= health_config.min_upstream_success_rate { CheckStatus::Ok } else { CheckStatus::Fail };
+            UpstreamCheck { status, success_rate: Some(rate), samples }
+        }
+        None => UpstreamCheck { status: CheckStatus::Ok, success_rate: None, samples: 0 },
+    }
+}
@@ -0,0 +1,23 @@
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use hyper::header;
+use warp::Reply;
+
+use super::Context;
+
+pub fn handle_get_metrics(context: Context) -> Response<Body> {
+    let (active, idle) = context.database.pool_usage();
+    context.metrics.set_db_connections(active, idle);
+    match context.metrics.encode() {
+        Ok(text) => {
+            let mut resp = Response::new(Body::from(text));
+            resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("text/plain; version=0.0.4"));
+            resp
+        }
+        Err(e) => {
+            tracing::error!("metrics encode error {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::convert::Infallible;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use hyper::Body;
 use hyper::Response;
+use tokio::sync::watch;
 use sqlx::database;
 use uuid::Uuid;
 use warp::Filter;
@@ -12,15 +14,43 @@ use warp::fs::File;
 use crate::client::MojangAPIRequester;
 use crate::client::config::UseCacheConfig;
 use crate::config::Config;
+use crate::metrics::Metrics;
 use crate::storage::NameHistoryDatabase;
+use crate::webhook::WebhookDispatcher;
 
+use self::apikey::Caller;
+use self::config::ApiKeyConfig;
 use self::config::ServerConfig;
+use self::error::ApiError;
+use self::events::EventChannel;
+use self::namehistory::ProfileFlights;
+use self::namehistory::ProfileRef;
+use self::namehistory::RefreshLimiter;
+use self::ratelimit::ClientKey;
+use self::ratelimit::ClientRateLimiter;
 
+pub mod admin;
+pub mod apikey;
+pub mod changes;
 pub mod config;
+pub mod error;
+pub mod events;
+pub mod format;
+pub mod health;
+pub mod metrics;
 pub mod namehistory;
+pub mod nameat;
+pub mod nameowners;
+pub mod namesearch;
+pub mod openapi;
+pub mod ratelimit;
+pub mod v2;
 
 static ROOT_INFO: &'static [u8] = b"Hyper Warp Server";
 
+/// minecraft names are at most 16 characters
+const MAX_NAME_LENGTH: usize = 16;
+
 async fn ctrl_c_signal() {
     // Wait for the CTRL+C signal
     tokio::signal::ctrl_c()
@@ -30,7 +60,8 @@ async fn ctrl_c_signal() {
 
 
 pub async fn server(config: &Config) {
-    let requester = MojangAPIRequester::new(&config.client);
+    let metrics = Metrics::new();
+    let requester = MojangAPIRequester::new(&config.client, metrics.clone());
     tracing::info!("requester running");
     let database = match NameHistoryDatabase::init(&config.database).await {
         Ok(v) => {
@@ -43,31 +74,193 @@ pub async fn server(config: &Config) {
         }
     };
     let addr = config.server.address.clone();
+    let rate_limiter = Arc::new(ClientRateLimiter::new(&config.server.rate_limit));
+    // after the path filters, so that requests falling through to later routes are only charged by the route they end up at
+    let trusted_proxies = Arc::new(config.server.rate_limit.trusted_proxies.clone());
+    let read_limit = ratelimit::read_limit(rate_limiter.clone(), trusted_proxies.clone());
     let root = warp::path::end()
+        .and(read_limit.clone())
         .map(|| { Response::new(Body::from(ROOT_INFO)) })
         .boxed();
     
     let static_files = if let Some(static_files_root) = &config.server.static_files {
-        warp::path("static").and(warp::fs::dir(static_files_root.clone())).boxed()
+        warp::path("static").and(read_limit.clone()).and(warp::fs::dir(static_files_root.clone())).boxed()
     } else {
         warp::path("static").and_then(reject_file).boxed()
     };
     let use_cache_config = Arc::new(config.client.use_cache.clone());
-    let name_history = warp::path("user").and(warp::path("profiles")).and(warp::path::param::<Uuid>()).and(warp::path("names")).and(warp::path::end())
-        .and(Context::new_in_filter(requester.clone(), database.clone(), use_cache_config.clone()))
+    let (shutdown_sender, shutdown_receiver) = watch::channel(());
+    let webhooks = WebhookDispatcher::new(&config.webhook, database.clone());
+    let webhook_worker = tokio::spawn(webhooks.clone().run(shutdown_receiver.clone()));
+    let context = Context {
+        requester,
+        database: database.clone(),
+        use_cache_config,
+        events: EventChannel::new(shutdown_receiver),
+        webhooks,
+        refresh_limiter: Arc::new(RefreshLimiter::new(&config.server.refresh)),
+        client: ClientKey::default(),
+        caller: Caller::Anonymous,
+        rate_limiter,
+        trusted_proxies,
+        api_key_config: Arc::new(config.server.api_keys.clone()),
+        metrics: metrics.clone(),
+        profile_flights: Arc::new(ProfileFlights::new()),
+        missing_no_content: config.server.missing_no_content,
+    };
+    let name_history = warp::path("user").and(warp::path("profiles")).and(warp::path::param::<String>()).and(warp::path("names")).and(warp::path::end())
+        .and_then(parse_profile)
+        .and(warp::query::<namehistory::NameHistoryQuery>())
+        .and(warp::header::optional::<String>("cache-control"))
+        .and(warp::header::headers_cloned())
+        .and(context.with_caller())
         .and_then(namehistory::handle_get_name_history)
         .boxed();
+    let name_history_v2 = warp::path("v2").and(warp::path("user")).and(warp::path("profiles")).and(warp::path::param::<String>()).and(warp::path("names")).and(warp::path::end())
+        .and_then(parse_profile)
+        .and(warp::query::<namehistory::NameHistoryQuery>())
+        .and(warp::header::optional::<String>("cache-control"))
+        .and(warp::header::headers_cloned())
+        .and(context.with_caller())
+        .and_then(v2::handle_get_name_history)
+        .boxed();
+    let name_at = warp::path("user").and(warp::path("profiles")).and(warp::path::param::<String>()).and(warp::path("name")).and(warp::path("at")).and(warp::path::param::<u64>()).and(warp::path::end())
+        .and_then(|uuid: String, at: u64| async move { Ok::<_, Rejection>((parse_uuid(uuid).await?, at)) }).untuple_one()
+        .and(context.with_caller())
+        .and_then(nameat::handle_get_name_at)
+        .boxed();
+    let name_search = warp::path("names").and(warp::path("search")).and(warp::path::end())
+        .and(warp::query::<namesearch::NameSearchQuery>())
+        .and(context.with_caller())
+        .and_then(namesearch::handle_search_names)
+        .boxed();
+    let changes = warp::path("changes").and(warp::path::end())
+        .and(warp::query::<changes::ChangesQuery>())
+        .and(context.with_caller())
+        .and_then(changes::handle_get_changes)
+        .boxed();
+    let events_sse = warp::path("events").and(warp::path::end())
+        .and(read_limit.clone())
+        .and(warp::query::<events::EventsQuery>())
+        .and(context.in_filter())
+        .map(events::handle_events_sse)
+        .boxed();
+    let events_ws = warp::path("ws").and(warp::path::end())
+        .and(read_limit.clone())
+        .and(warp::ws())
+        .and(warp::query::<events::EventsQuery>())
+        .and(context.in_filter())
+        .map(events::handle_events_ws)
+        .boxed();
+    let metrics_endpoint = warp::path("metrics").and(warp::path::end())
+        .and(read_limit.clone())
+        .and(context.in_filter())
+        .map(metrics::handle_get_metrics)
+        .boxed();
+    let openapi_document = warp::path("openapi.json").and(warp::path::end())
+        .and(read_limit.clone())
+        .map(openapi::handle_get_openapi)
+        .boxed();
+    let healthz = warp::path("healthz").and(warp::path::end())
+        .map(health::handle_get_healthz)
+        .boxed();
+    let health_config = Arc::new(config.server.health.clone());
+    let readyz = warp::path("readyz").and(warp::path::end())
+        .and(warp::any().map(move || health_config.clone()))
+        .and(context.in_filter())
+        .and_then(health::handle_get_readyz)
+        .boxed();
+    let bulk_config = Arc::new(config.server.bulk.clone());
+    let body_limit = (bulk_config.max_uuids as u64 + 1) * 64;
+    let name_histories = warp::path("user").and(warp::path("profiles")).and(warp::path("names")).and(warp::path::end())
+        .and(warp::body::content_length_limit(body_limit)).and(warp::body::json::<Vec<Uuid>>())
+        .and(warp::query::<format::FormatQuery>())
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(move || bulk_config.clone()))
+        .and(context.with_caller())
+        .and_then(namehistory::handle_get_name_histories)
+        .boxed();
+    let name_owners = warp::path("users").and(warp::path("profiles")).and(warp::path("minecraft")).and(warp::path::param::<String>()).and(warp::path::end())
+        .and(warp::query::<nameowners::NameOwnersQuery>())
+        .and(context.with_caller())
+        .and_then(nameowners::handle_get_name_owners)
+        .boxed();
+    let admin_config = Arc::new(config.server.admin.clone());
+    let admin_config = warp::any().map(move || admin_config.clone());
+    let authorization = warp::header::optional::<String>("authorization");
+    let admin_insert_name = warp::post().and(warp::path("admin")).and(warp::path("user")).and(warp::path("profiles")).and(warp::path::param::<Uuid>()).and(warp::path("names")).and(warp::path::end())
+        .and(authorization).and(warp::body::content_length_limit(4096)).and(warp::body::json::<admin::AdminNameBody>())
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_insert_name)
+        .boxed();
+    let admin_edit_name = warp::put().and(warp::path("admin")).and(warp::path("names")).and(warp::path::param::<i64>()).and(warp::path::end())
+        .and(authorization).and(warp::body::content_length_limit(4096)).and(warp::body::json::<admin::AdminNameBody>())
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_edit_name)
+        .boxed();
+    let admin_delete_name = warp::delete().and(warp::path("admin")).and(warp::path("names")).and(warp::path::param::<i64>()).and(warp::path::end())
+        .and(authorization)
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_delete_name)
+        .boxed();
+    let admin_reset_update = warp::delete().and(warp::path("admin")).and(warp::path("user")).and(warp::path("profiles")).and(warp::path::param::<Uuid>()).and(warp::path("update")).and(warp::path::end())
+        .and(authorization)
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_reset_update)
+        .boxed();
+    let admin_create_api_key = warp::post().and(warp::path("admin")).and(warp::path("apikeys")).and(warp::path::end())
+        .and(authorization).and(warp::body::content_length_limit(4096)).and(warp::body::json::<admin::AdminApiKeyBody>())
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_create_api_key)
+        .boxed();
+    let admin_get_api_keys = warp::get().and(warp::path("admin")).and(warp::path("apikeys")).and(warp::path::end())
+        .and(authorization)
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_get_api_keys)
+        .boxed();
+    let admin_delete_api_key = warp::delete().and(warp::path("admin")).and(warp::path("apikeys")).and(warp::path::param::<i64>()).and(warp::path::end())
+        .and(authorization)
+        .and(admin_config.clone())
+        .and(context.in_filter())
+        .and_then(admin::handle_delete_api_key)
+        .boxed();
 
+    // the api routes spend the read budget in `with_caller`, once the caller is known
     let get_router = warp::get()
-        .and(root.or(name_history).or(static_files))
+        .and(name_history.or(name_at).or(name_owners).or(name_search).or(changes).or(name_history_v2));
+    let misc_router = warp::get()
+        .and(root.or(metrics_endpoint).or(openapi_document).or(events_sse).or(events_ws).or(static_files));
+    // probes are not rate limited
+    let health_router = warp::get()
+        .and(healthz.or(readyz));
+    let post_router = warp::post()
+        .and(name_histories);
+    let admin_router = admin_insert_name.or(admin_edit_name).or(admin_delete_name).or(admin_reset_update)
+        .or(admin_create_api_key).or(admin_get_api_keys).or(admin_delete_api_key);
+    let router = get_router.or(health_router).or(post_router).or(admin_router).or(misc_router)
+        .recover(error::recover)
+        .with(warp::log::custom(move |info| metrics.observe_http(route_name(info.path()), info.method().as_str(), info.status().as_u16(), info.elapsed())))
         .with(warp::trace::request());
         // TODO: change with as better log
 
-    let (addr, server) = warp::serve(get_router).bind_with_graceful_shutdown(addr, ctrl_c_signal());
+    let (addr, server) = warp::serve(router).bind_with_graceful_shutdown(addr, async move {
+        ctrl_c_signal().await;
+        // ends the open event streams, which would otherwise keep the server alive
+        drop(shutdown_sender);
+    });
     tracing::info!("server started @{}", &addr);
 
     server.await;
     tracing::info!("server stopped");
+    if let Err(e) = webhook_worker.await {
+        tracing::error!("webhook worker error: {}", e);
+    }
     database.close().await;
     tracing::info!("database closed");
 }
@@ -78,12 +271,58 @@ pub struct Context {
     pub(crate) requester: MojangAPIRequester,
     pub(crate) database: NameHistoryDatabase,
     pub(crate) use_cache_config: Arc<UseCacheConfig>,
+    pub(crate) events: EventChannel,
+    pub(crate) webhooks: WebhookDispatcher,
+    pub(crate) refresh_limiter: Arc<RefreshLimiter>,
+    /// the caller of the current request, set by `in_filter` and `with_caller`
+    pub(crate) client: ClientKey,
+    pub(crate) caller: Caller,
+    pub(crate) rate_limiter: Arc<ClientRateLimiter>,
+    pub(crate) trusted_proxies: Arc<Vec<IpAddr>>,
+    pub(crate) api_key_config: Arc<ApiKeyConfig>,
+    pub(crate) metrics: Metrics,
+    pub(crate) profile_flights: Arc<ProfileFlights>,
+    pub(crate) missing_no_content: bool,
 }
 
 impl Context {
     
-    pub fn new_in_filter(requester: MojangAPIRequester, database: NameHistoryDatabase, use_cache_config: Arc<UseCacheConfig>) -> impl Filter<Extract = (Self, ), Error = Infallible> + Clone {
-        warp::any().map(move || Context { requester: requester.clone(), database: database.clone(), use_cache_config: use_cache_config.clone() })
+    pub fn in_filter(&self) -> impl Filter<Extract = (Self, ), Error = Infallible> + Clone {
+        let context = self.clone();
+        ratelimit::client_key(self.trusted_proxies.clone()).map(move |client| Self { client, ..context.clone() })
+    }
+
+    /// like `in_filter`, but also resolves the api key of the request; see `apikey::resolve_caller`
+    pub fn with_caller(&self) -> impl Filter<Extract = (Self, ), Error = Rejection> + Clone {
+        self.in_filter()
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::query::<apikey::ApiKeyQuery>())
+            .and_then(apikey::resolve_caller)
+    }
+}
+
+
+/// the route a path belongs to, as the `route` label of the request metrics; keeps uuids and names out of the labels
+fn route_name(path: &str) -> &'static str {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        [""] => "root",
+        ["user", "profiles", "names"] => "name_histories",
+        ["user", "profiles", _, "names"] => "name_history",
+        ["user", "profiles", _, "name", "at", _] => "name_at",
+        ["v2", "user", "profiles", _, "names"] => "v2_name_history",
+        ["users", "profiles", "minecraft", _] => "name_owners",
+        ["names", "search"] => "name_search",
+        ["changes"] => "changes",
+        ["events"] => "events",
+        ["ws"] => "ws",
+        ["metrics"] => "metrics",
+        ["openapi.json"] => "openapi",
+        ["healthz"] => "healthz",
+        ["readyz"] => "readyz",
+        ["admin", ..] => "admin",
+        ["static", ..] => "static",
+        _ => "other",
     }
 }
 
@@ -91,3 +330,19 @@ impl Context {
 pub(crate) async fn reject_file() -> Result<File, Rejection> {
     Err(warp::reject())
 }
+
+/// a path segment that must be a uuid; unlike `warp::path::param::<Uuid>()` a malformed one is a 400 and not a 404
+pub(crate) async fn parse_uuid(s: String) -> Result<Uuid, Rejection> {
+    s.parse().map_err(|_| warp::reject::custom(ApiError::InvalidUuid(s)))
+}
+
+/// a path segment that is a uuid or a player name
+pub(crate) async fn parse_profile(s: String) -> Result<ProfileRef, Rejection> {
+    if let Ok(uuid) = s.parse() {
+        Ok(ProfileRef::Uuid(uuid))
+    } else if s.len() <= MAX_NAME_LENGTH && nameowners::is_valid_name(s.as_str()) {
+        Ok(ProfileRef::Name(s))
+    } else {
+        Err(warp::reject::custom(ApiError::InvalidUuid(s)))
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use serde::Serialize;
+use uuid::Uuid;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::storage::data::NameRecord;
+use crate::storage::data::into_millis;
+
+use super::Context;
+use super::error::ApiError;
+use super::namehistory::UPDATE_BY_PROFILE;
+use super::namehistory::handle_get_name_history_inner;
+
+/// the name held at some moment, together with the interval it was held in.
+///
+/// `exact` is false when a bound of that interval is only the time the service observed a change
+/// (the real change happened at some point before it), or when the moment lies before the first observation.
+#[derive(Debug, Serialize)]
+pub struct NameAt {
+
+    pub name: String,
+
+    #[serde(rename = "changedToAt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_to_at: Option<u128>,
+
+    #[serde(rename = "changedAwayAt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_away_at: Option<u128>,
+
+    pub exact: bool,
+}
+
+pub async fn handle_get_name_at(uuid: Uuid, at: u64, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_get_name_at_inner(uuid, at, context).await {
+        Ok(Some(data)) => Ok(warp::reply::json(&data).into_response()),
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+
+async fn handle_get_name_at_inner(uuid: Uuid, at: u64, context: Context) -> Result<Option<NameAt>, ApiError> {
+    // refresh through the usual cache policy first, so a recent `at` sees the current name
+    handle_get_name_history_inner(uuid, false, context.clone()).await?;
+    let records = context.database.get_name_records(&uuid).await?;
+    let at = SystemTime::UNIX_EPOCH + Duration::from_millis(at);
+    Ok(find_name_at(records.as_slice(), &at))
+}
+
+/// false if `changed_to_at` of `record` is only the time the service observed the change
+pub fn is_exact(record: &NameRecord) -> bool {
+    record.changed_to_at.is_none() || record.source != UPDATE_BY_PROFILE
+}
+
+/// `records` must be ordered by `changedToAt`, the initial name (without `changedToAt`) first
+pub fn find_name_at(records: &[NameRecord], at: &SystemTime) -> Option<NameAt> {
+    let pos = records.iter().rposition(|r| r.changed_to_at.as_ref().map(|t| t <= at).unwrap_or(true));
+    let current = records.get(pos.unwrap_or(0))?;
+    let next = pos.and_then(|i| records.get(i + 1));
+    let exact_since = is_exact(current);
+    let exact_until = next.map(|r| r.source != UPDATE_BY_PROFILE).unwrap_or(true);
+    Some(NameAt {
+        name: current.name.clone(),
+        changed_to_at: current.changed_to_at.as_ref().and_then(into_millis),
+        changed_away_at: next.and_then(|r| r.changed_to_at.as_ref()).and_then(into_millis),
+        exact: pos.is_some() && exact_since && exact_until,
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn record(index: i64, name: &str, changed_to_at: Option<u64>, source: u32) -> NameRecord {
+        NameRecord {
+            index,
+            uuid: Uuid::nil(),
+            name: name.to_string(),
+            changed_to_at: changed_to_at.map(|t| SystemTime::UNIX_EPOCH + Duration::from_millis(t)),
+            source,
+        }
+    }
+
+    #[test]
+    fn name_at() {
+        let imported = UPDATE_BY_PROFILE + 1;
+        let records = vec![
+            record(1, "a", None, imported),
+            record(2, "b", Some(1000), imported),
+            record(3, "c", Some(2000), UPDATE_BY_PROFILE),
+        ];
+        let at = |t: u64| find_name_at(records.as_slice(), &(SystemTime::UNIX_EPOCH + Duration::from_millis(t))).unwrap();
+        let r = at(500);
+        assert_eq!((r.name.as_str(), r.exact), ("a", true));
+        let r = at(1000);
+        assert_eq!((r.name.as_str(), r.exact, r.changed_away_at), ("b", false, Some(2000)));
+        let r = at(3000);
+        assert_eq!((r.name.as_str(), r.exact), ("c", false));
+
+        let observed = vec![record(4, "d", Some(1000), UPDATE_BY_PROFILE)];
+        let r = find_name_at(observed.as_slice(), &SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!((r.name.as_str(), r.exact), ("d", false));
+        assert!(find_name_at(&[], &SystemTime::UNIX_EPOCH).is_none());
+    }
+}
@@ -1,12 +1,26 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
 use std::time::SystemTime;
 
+use futures_util::future::join_all;
+use headers::CacheControl;
+use headers::ETag;
+use headers::HeaderMapExt;
+use headers::IfModifiedSince;
+use headers::IfNoneMatch;
+use headers::LastModified;
+use hyper::HeaderMap;
 use hyper::header;
 use hyper::Response;
 use hyper::Body;
 use hyper::StatusCode;
 use hyper::http::request;
-use serde::Serialize;
-use serde::ser::SerializeStruct;
+use serde::Deserialize;
+use sha2::Digest;
+use sha2::Sha256;
+use utoipa::IntoParams;
 use uuid::Uuid;
 use warp::Rejection;
 use warp::Reply;
@@ -15,156 +29,494 @@ use crate::client::JsonRequesterError;
 use crate::client::data::Profile;
 use crate::storage::data::NameHistory;
 use crate::storage::data::NameHistoryElement;
+use crate::storage::data::PERMISSION_REFRESH;
 use crate::storage::data::Update;
+use crate::storage::data::into_millis;
+use crate::utils::ratelimit::RateLimiter;
+use crate::utils::singleflight::SingleFlight;
 
 use super::Context;
+use super::apikey::Caller;
+use super::config::BulkConfig;
+use super::config::RefreshConfig;
+use super::error::ApiError;
+use super::format;
+use super::format::Format;
+use super::format::FormatQuery;
+use super::format::Records;
+use super::nameowners::resolve_name;
+use super::events::NameChangeEvent;
+use super::ratelimit::ClientKey;
 
 pub const UPDATE_BY_PROFILE: u32 = 1;
+pub const UPDATE_BY_MANUAL: u32 = 2;
 
-pub async fn handle_get_name_history(uuid: Uuid, context: Context) -> Result<Response<Body>, Rejection> {
-    match handle_get_name_history_inner(uuid, context).await {
-        Ok(data) => Ok(warp::reply::json(&data).into_response()),
-        Err(resp) => Ok(resp)
+pub const X_CACHE: &'static str = "X-Cache";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NameHistoryQuery {
+    /// skip the `use_cache` check, same as `Cache-Control: no-cache`
+    #[serde(default)]
+    pub refresh: bool,
+    /// overrides `Accept`
+    pub format: Option<Format>,
+}
+
+pub struct RefreshLimiter {
+    per_client: RateLimiter<ClientKey>,
+    per_uuid: RateLimiter<Uuid>,
+}
+
+impl RefreshLimiter {
+
+    pub fn new(config: &RefreshConfig) -> Self {
+        Self {
+            per_client: RateLimiter::new(config.client_burst, config.client_period),
+            per_uuid: RateLimiter::new(1, config.uuid_period),
+        }
     }
 }
 
+pub type ProfileFlights = SingleFlight<Uuid, FetchResult>;
 
-async fn handle_get_name_history_inner(uuid: Uuid, context: Context) -> Result<NameHistory, Response<Body>> {
-    let now = SystemTime::now();
-    let update = context.database.get_update(&uuid).await.map_err(into_error_response_db)?;
-    let (no_update_record, need_request) = if let Some(update) = update {
-        (false, !update.use_cache(&now, context.use_cache_config.as_ref()))
-    } else {
-        (true, true)
+/// the `{uuid}` segment of the history routes: a uuid, or the current name of a profile
+#[derive(Debug, Clone)]
+pub enum ProfileRef {
+    Uuid(Uuid),
+    Name(String),
+}
+
+impl ProfileRef {
+
+    /// a name nobody has is answered like a profile upstream does not know
+    pub(crate) async fn resolve(self, context: &Context) -> Result<Uuid, ApiError> {
+        match self {
+            Self::Uuid(uuid) => Ok(uuid),
+            Self::Name(name) => resolve_name(name.as_str(), context).await?
+                .ok_or(ApiError::ProfileNotFound { tombstone: None, legacy: context.missing_no_content }),
+        }
+    }
+}
+
+/// the names a profile has had, the first known one first
+#[utoipa::path(
+    get,
+    path = "/user/profiles/{uuid}/names",
+    tag = "names",
+    params(
+        ("uuid" = String, Path, description = "uuid of the profile, with or without hyphens, or its current name"),
+        NameHistoryQuery,
+        ("key" = Option<String>, Query, description = "api key, instead of `Authorization: Bearer <key>`"),
+        ("Cache-Control" = Option<String>, Header, description = "`no-cache` skips the `use_cache` check, same as `refresh=true`"),
+    ),
+    responses(
+        (status = 200, description = "name history, see `format`", content(
+            ("application/json" = [NameHistoryElement]),
+            ("text/csv" = String),
+            ("application/x-ndjson" = String),
+            ("application/msgpack" = [NameHistoryElement]),
+        ), headers(
+            ("ETag" = String),
+            ("Last-Modified" = String, description = "the last check against upstream"),
+            ("Cache-Control" = String, description = "`private` if an api key is used or required, else `public`; `max-age` is the time left until the next check"),
+            ("Warning" = String, description = "`110` if the history is stale"),
+            ("X-Cache" = String, description = "`STALE` if the history is served past its TTL, while it is refreshed, because upstream failed or because the client is out of upstream budget"),
+        )),
+        (status = 204, description = "no such profile, if `missing_no_content` is set"),
+        (status = 304, description = "the history matches `If-None-Match` or `If-Modified-Since`"),
+        (status = 400, description = "`invalid_uuid`", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "`unauthorized`", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "`forbidden`", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "`profile_not_found`, with a tombstone if the profile existed before", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "`rate_limited`, `refresh_limited` or `quota_exceeded`", body = Problem, content_type = "application/problem+json"),
+        (status = 500, description = "`database_error`", body = Problem, content_type = "application/problem+json"),
+        (status = 502, description = "`upstream_unavailable` or `upstream_decode`", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "`upstream_status`", body = Problem, content_type = "application/problem+json"),
+        (status = 504, description = "`upstream_timeout`", body = Problem, content_type = "application/problem+json"),
+    ),
+    security((), ("api_key" = [])),
+)]
+pub async fn handle_get_name_history(profile: ProfileRef, query: NameHistoryQuery, cache_control: Option<String>, headers: HeaderMap, context: Context) -> Result<Response<Body>, Rejection> {
+    let uuid = match profile.resolve(&context).await {
+        Ok(uuid) => uuid,
+        Err(e) => return Ok(e.into_response()),
     };
-    let mut data = context.database.get_name_history(&uuid).await.map_err(into_error_response_db)?;
-    if need_request {
-        let profile = context.requester.request_profile(&uuid).await.map_err(into_error_response_req)?;
-        tracing::debug!("request new profile @{}", &uuid);
-        let mut update_record = Update::new(now, false);
-        let need_update = if let Some(last) = data.last() {
-            if last.name == profile.name {
-                None
-            } else {
-                Some(NameHistoryElement::new(profile.name, now))
+    let force = match check_force(&uuid, &query, cache_control.as_deref(), &context).await {
+        Ok(force) => force,
+        Err(e) => return Ok(e.into_response()),
+    };
+    match handle_get_name_history_inner(uuid, force, context.clone()).await {
+        Ok(lookup) => Ok(reply_cacheable(&lookup.data, &lookup, Format::negotiate(query.format, &headers), &headers, &context)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+/// whether the request asks to skip the cache and is allowed to, see `check_refresh`
+pub(crate) async fn check_force(uuid: &Uuid, query: &NameHistoryQuery, cache_control: Option<&str>, context: &Context) -> Result<bool, ApiError> {
+    if query.refresh || cache_control.map(is_no_cache).unwrap_or(false) {
+        check_refresh(uuid, context).await
+    } else {
+        Ok(false)
+    }
+}
+
+
+/// a name history as served, with the update record it was served under
+pub(crate) struct NameHistoryLookup {
+    pub data: NameHistory,
+    pub update: Update,
+    /// served past its `use_cache` TTL: while it is revalidated in the background, or because upstream failed or was not asked
+    pub stale: bool,
+}
+
+/// 404 (or 204, see `ServerConfig::missing_no_content`) for a profile upstream does not know
+pub(crate) async fn handle_get_name_history_inner(uuid: Uuid, force: bool, context: Context) -> Result<NameHistoryLookup, ApiError> {
+    let lookup = lookup_name_history(uuid, force, &context).await?;
+    if lookup.update.missing {
+        return Err(profile_not_found(&uuid, &context).await);
+    }
+    Ok(lookup)
+}
+
+async fn lookup_name_history(uuid: Uuid, force: bool, context: &Context) -> Result<NameHistoryLookup, ApiError> {
+    let now = SystemTime::now();
+    let update = context.database.get_update(&uuid).await?;
+    let cached = !force && !need_request(update.as_ref(), &now, context);
+    match update {
+        Some(update) if cached => {
+            let data = context.database.get_name_history(&uuid).await?;
+            Ok(NameHistoryLookup { data, update, stale: false })
+        }
+        Some(update) if !force && update.use_cache_while_revalidate(&now, context.use_cache_config.as_ref()) => {
+            let data = context.database.get_name_history(&uuid).await?;
+            // a client out of upstream budget still gets the cached history, only without the refresh
+            if context.rate_limiter.check_upstream(&context.client).is_ok() {
+                spawn_revalidate(uuid, context);
             }
-        } else {
-            Some(NameHistoryElement::new(profile.name, now))
-        };
-        if let Some(record) = need_update {
-            update_record.changed = true;
-            context.database.add_name_history(&uuid, &record, UPDATE_BY_PROFILE).await.map_err(into_error_response_db)?;
-            tracing::debug!("update @{}: {:?}", &uuid, &record);
-            data.push(record);
+            Ok(NameHistoryLookup { data, update, stale: true })
         }
-        if no_update_record {
-            context.database.insert_update(&uuid, &update_record).await.map_err(into_error_response_db)?;
-        } else {
-            context.database.refresh_update(&uuid, &update_record).await.map_err(into_error_response_db)?;
+        update => {
+            // a client out of upstream budget is served like a failed request, from the stale history if there is one
+            let e = match check_upstream(context) {
+                Ok(()) => match fetch_profile(uuid, context).await {
+                    Ok((data, update)) => return Ok(NameHistoryLookup { data, update, stale: false }),
+                    Err(e) => match e.as_ref() {
+                        FetchError::Request(_) => ApiError::from(e.as_ref()),
+                        FetchError::Database(_) => return Err(ApiError::from(e.as_ref())),
+                    }
+                }
+                Err(e) => e,
+            };
+            match update {
+                Some(update) if within_max_stale(&update, &now, context) => {
+                    let data = context.database.get_name_history(&uuid).await?;
+                    if data.is_empty() && !update.missing {
+                        return Err(e);
+                    }
+                    tracing::warn!("refresh profile @{} failed, serve stale history: {}", &uuid, e);
+                    Ok(NameHistoryLookup { data, update, stale: true })
+                }
+                _ => Err(e),
+            }
         }
     }
-    Ok(data)
 }
 
-pub(crate) struct ErrorWrapper<E>(E);
+fn spawn_revalidate(uuid: Uuid, context: &Context) {
+    let context = context.clone();
+    tokio::spawn(async move {
+        match fetch_profile(uuid, &context).await {
+            Ok(_) => tracing::debug!("revalidated profile @{}", &uuid),
+            Err(e) => match e.as_ref() {
+                FetchError::Request(e) => tracing::warn!("revalidate profile @{} failed: {}", &uuid, e),
+                FetchError::Database(e) => tracing::error!("revalidate profile @{} database error {}", &uuid, e),
+            }
+        }
+    });
+}
+
+fn within_max_stale(update: &Update, now: &SystemTime, context: &Context) -> bool {
+    now.duration_since(update.update).map(|d| d <= context.use_cache_config.max_stale).unwrap_or(true)
+}
 
-pub(crate) fn into_error_response_db(e: sqlx::Error) -> Response<Body> {
-    tracing::error!("database error {}", &e);
-    let e = ErrorWrapper(e);
-    match serde_json::to_vec(&e) {
-        Ok(body) => {
-            let mut resp = Response::new(body.into());
-            resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
-            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-            resp
+/// what one upstream refresh of a profile produced, shared by every caller that waited on it
+pub(crate) type FetchResult = Result<(NameHistory, Update), Arc<FetchError>>;
+
+pub(crate) enum FetchError {
+    Request(JsonRequesterError),
+    Database(sqlx::Error),
+}
+
+/// requests the profile and records it; concurrent calls for the same uuid share one upstream request and one database write
+pub(crate) async fn fetch_profile(uuid: Uuid, context: &Context) -> FetchResult {
+    let flight_context = context.clone();
+    context.profile_flights.run(uuid, async move {
+        fetch_profile_inner(uuid, flight_context).await.map_err(Arc::new)
+    }).await
+}
+
+async fn fetch_profile_inner(uuid: Uuid, context: Context) -> Result<(NameHistory, Update), FetchError> {
+    let now = SystemTime::now();
+    // read inside the flight, so a flight that just finished is seen and `insert_update` is not run twice
+    let update = context.database.get_update(&uuid).await.map_err(FetchError::Database)?;
+    let mut data = context.database.get_name_history(&uuid).await.map_err(FetchError::Database)?;
+    let profile = context.requester.request_profile(&uuid).await.map_err(FetchError::Request)?;
+    tracing::debug!("request new profile @{}", &uuid);
+    let update = match profile {
+        Some(profile) => {
+            if update.as_ref().map(|u| u.missing).unwrap_or(false) {
+                context.database.remove_tombstone(&uuid).await.map_err(FetchError::Database)?;
+            }
+            record_profile(&uuid, now, update.is_none(), profile, &mut data, &context).await
         }
-        Err(e) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    }
-}
-
-pub(crate) fn into_error_response_req(e: JsonRequesterError) -> Response<Body> {
-    let s = match &e {
-        JsonRequesterError::StatusCode(s) => {
-            tracing::warn!("request failed: {}", s);
-            StatusCode::SERVICE_UNAVAILABLE
-        },
-        JsonRequesterError::Hyper(e) => {
-            tracing::error!("request error: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        },
-        JsonRequesterError::Deserialize(e) => {
-            tracing::error!("request error: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        },
+        None => record_missing(&uuid, now, update.as_ref(), &data, &context).await,
     };
-    let e = ErrorWrapper(e);
-    match serde_json::to_vec(&e) {
-        Ok(body) => {
-            let mut resp = Response::new(body.into());
-            resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
-            *resp.status_mut() = s;
-            resp
+    Ok((data, update.map_err(FetchError::Database)?))
+}
+
+/// caches that upstream has no such profile; if it had one before, a tombstone keeps the last name it had
+async fn record_missing(uuid: &Uuid, now: SystemTime, previous: Option<&Update>, data: &NameHistory, context: &Context) -> Result<Update, sqlx::Error> {
+    let update_record = Update::new_missing(now);
+    let disappeared = !data.is_empty() && !previous.map(|u| u.missing).unwrap_or(false);
+    if disappeared {
+        let last_name = data.last().map(|last| last.name.as_str());
+        context.database.add_tombstone(uuid, last_name, &now).await?;
+        tracing::info!("profile @{} disappeared, last name {:?}", uuid, last_name);
+    }
+    if previous.is_none() {
+        context.database.insert_update(uuid, &update_record).await?;
+    } else {
+        context.database.refresh_update(uuid, &update_record).await?;
+    }
+    Ok(update_record)
+}
+
+async fn profile_not_found(uuid: &Uuid, context: &Context) -> ApiError {
+    if context.missing_no_content {
+        return ApiError::ProfileNotFound { tombstone: None, legacy: true };
+    }
+    match context.database.get_tombstone(uuid).await {
+        Ok(tombstone) => ApiError::ProfileNotFound { tombstone, legacy: false },
+        Err(e) => ApiError::from(e),
+    }
+}
+
+/// reply of `data` in `format` with `ETag`, `Last-Modified` (the last upstream check), `Cache-Control: max-age` (the time left under `use_cache`)
+/// and `Warning` / `X-Cache: STALE` if `lookup` is past that TTL, or 304 if the request's conditional headers match
+pub(crate) fn reply_cacheable<T: Records>(data: &T, lookup: &NameHistoryLookup, format: Format, headers: &HeaderMap, context: &Context) -> Response<Body> {
+    let update = &lookup.update;
+    let body = match format::encode(data, format) {
+        Ok(body) => body,
+        Err(e) => return e.into_response(),
+    };
+    let digest = Sha256::digest(body.as_slice());
+    let etag = format!("\"{}\"", digest[..16].iter().map(|b| format!("{:02x}", b)).collect::<String>());
+    let etag = etag.parse::<ETag>().expect("hex digest is a valid entity tag");
+    let last_modified = LastModified::from(update.update);
+    let max_age = Duration::from_secs(update.expires_in(&SystemTime::now(), context.use_cache_config.as_ref()).as_secs());
+    let not_modified = if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
+        !if_none_match.precondition_passes(&etag)
+    } else if let Some(if_modified_since) = headers.typed_get::<IfModifiedSince>() {
+        !if_modified_since.is_modified(update.update)
+    } else {
+        false
+    };
+    let mut resp = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut resp = Response::new(Body::from(body));
+        resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(format.content_type()));
+        resp
+    };
+    resp.headers_mut().insert(header::VARY, header::HeaderValue::from_static("accept, authorization"));
+    resp.headers_mut().typed_insert(etag);
+    resp.headers_mut().typed_insert(last_modified);
+    // a shared cache must not answer for a key, nor let callers without one skip the key check
+    let cache_control = match (&context.caller, context.api_key_config.allow_anonymous) {
+        (Caller::Anonymous, true) => CacheControl::new().with_public(),
+        _ => CacheControl::new().with_private(),
+    };
+    resp.headers_mut().typed_insert(cache_control.with_max_age(max_age));
+    if lookup.stale {
+        resp.headers_mut().insert(header::WARNING, header::HeaderValue::from_static("110 - \"Response is Stale\""));
+        resp.headers_mut().insert(X_CACHE, header::HeaderValue::from_static("STALE"));
+    }
+    resp
+}
+
+/// a key without the refresh permission gets 403; a uuid refreshed just before, or one asked for by a client over its budget, is served
+/// through the usual cache policy, and the client gets 429 only if nothing is cached yet
+async fn check_refresh(uuid: &Uuid, context: &Context) -> Result<bool, ApiError> {
+    if !context.caller.allows(PERMISSION_REFRESH) {
+        return Err(ApiError::Forbidden("api key lacks the refresh permission"));
+    }
+    if let Err(wait) = context.refresh_limiter.per_client.check(context.client.clone()) {
+        tracing::debug!("refresh @{} rejected for {}", uuid, &context.client);
+        if context.database.get_update(uuid).await?.is_none() {
+            return Err(ApiError::RefreshLimited(wait));
         }
-        Err(e) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    }
-}
-
-
-impl Serialize for ErrorWrapper<JsonRequesterError> {
-
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer 
-    {
-        let mut s = serializer.serialize_struct("ErrorWrapper", 2)?;
-        match &self.0 {
-            JsonRequesterError::Deserialize(e) => {
-                s.serialize_field("type", "inner")?;
-                s.serialize_field("error", e.to_string().as_str())?;
-            },
-            JsonRequesterError::Hyper(e) => {
-                s.serialize_field("type", "request")?;
-                s.serialize_field("error", e.to_string().as_str())?;
-            },
-            JsonRequesterError::StatusCode(c) => {
-                s.serialize_field("type", "request")?;
-                s.serialize_field("code", &c.as_u16())?;
-            },
+        return Ok(false);
+    }
+    Ok(context.refresh_limiter.per_uuid.check(*uuid).is_ok())
+}
+
+/// spends one token of the client's upstream budget; 429 once it is gone
+pub(crate) fn check_upstream(context: &Context) -> Result<(), ApiError> {
+    context.rate_limiter.check_upstream(&context.client).map_err(|wait| {
+        tracing::debug!("upstream limit hit by {}", &context.client);
+        ApiError::RateLimited(wait)
+    })
+}
+
+fn is_no_cache(cache_control: &str) -> bool {
+    cache_control.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("no-cache"))
+}
+
+pub async fn handle_get_name_histories(uuids: Vec<Uuid>, query: FormatQuery, headers: HeaderMap, bulk_config: Arc<BulkConfig>, context: Context) -> Result<Response<Body>, Rejection> {
+    if uuids.len() > bulk_config.max_uuids {
+        return Ok(ApiError::PayloadTooLarge(format!("too many uuids: {} > {}", uuids.len(), bulk_config.max_uuids)).into_response());
+    }
+    match handle_get_name_histories_inner(uuids, bulk_config.as_ref(), context).await {
+        Ok(data) => Ok(format::reply(&data, Format::negotiate(query.format, &headers))),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+async fn handle_get_name_histories_inner(mut uuids: Vec<Uuid>, bulk_config: &BulkConfig, context: Context) -> Result<HashMap<Uuid, NameHistory>, ApiError> {
+    let now = SystemTime::now();
+    uuids.sort();
+    uuids.dedup();
+    let updates = context.database.get_updates(uuids.as_slice()).await?;
+    let mut histories = context.database.get_name_histories(uuids.as_slice()).await?;
+    let stale = uuids.iter()
+        .filter(|uuid| need_request(updates.get(uuid), &now, &context))
+        .collect::<Vec<_>>();
+    if stale.len() > bulk_config.max_upstream_requests {
+        tracing::debug!("bulk request skip {} stale profiles", stale.len() - bulk_config.max_upstream_requests);
+    }
+    let stale = &stale[..stale.len().min(bulk_config.max_upstream_requests)];
+    // past the client's upstream budget the remaining profiles are served from the cache
+    let allowed = stale.iter().take_while(|_| context.rate_limiter.check_upstream(&context.client).is_ok()).count();
+    if allowed < stale.len() {
+        tracing::debug!("bulk request skip {} stale profiles over the upstream limit of {}", stale.len() - allowed, &context.client);
+    }
+    let stale = &stale[..allowed];
+    let mut missing = updates.iter()
+        .filter(|(_, update)| update.missing)
+        .map(|(uuid, _)| *uuid)
+        .collect::<HashSet<_>>();
+    let fetched = join_all(stale.iter().map(|uuid| fetch_profile(**uuid, &context))).await;
+    for (uuid, result) in stale.iter().zip(fetched) {
+        match result {
+            Ok((data, update)) => {
+                if update.missing {
+                    missing.insert(**uuid);
+                } else {
+                    missing.remove(*uuid);
+                }
+                histories.insert(**uuid, data);
+            }
+            Err(e) => match e.as_ref() {
+                FetchError::Request(e) => {
+                    // one failed profile should not fail the whole batch; serve what is cached
+                    tracing::warn!("request profile @{} failed: {}", uuid, e);
+                }
+                FetchError::Database(e) => return Err(ApiError::Database(e.to_string())),
+            }
+        }
+    }
+    histories.retain(|uuid, data| !data.is_empty() && !missing.contains(uuid));
+    Ok(histories)
+}
+
+fn need_request(update: Option<&Update>, now: &SystemTime, context: &Context) -> bool {
+    let need = if let Some(update) = update {
+        !update.use_cache(now, context.use_cache_config.as_ref())
+    } else {
+        true
+    };
+    context.metrics.observe_cache(!need);
+    need
+}
+
+async fn record_profile(uuid: &Uuid, now: SystemTime, no_update_record: bool, profile: Profile, data: &mut NameHistory, context: &Context) -> Result<Update, sqlx::Error> {
+    let mut update_record = Update::new(now, false);
+    let need_update = if let Some(last) = data.last() {
+        if last.name == profile.name {
+            None
+        } else {
+            Some(NameHistoryElement::new(profile.name, now))
         }
-        s.end()
+    } else {
+        Some(NameHistoryElement::new(profile.name, now))
+    };
+    if let Some(record) = need_update {
+        update_record.changed = true;
+        context.database.add_name_history(uuid, &record, UPDATE_BY_PROFILE).await?;
+        tracing::debug!("update @{}: {:?}", uuid, &record);
+        if let Some(event) = name_change_event(uuid, data, &record) {
+            context.metrics.inc_name_changes();
+            match serde_json::to_string(&event) {
+                Ok(payload) => {
+                    // the change itself is already stored, so a failed enqueue must not fail the request
+                    if let Err(e) = context.webhooks.enqueue(uuid, payload.as_str()).await {
+                        tracing::error!("webhook enqueue error @{}: {}", uuid, e);
+                    }
+                }
+                Err(e) => tracing::error!("event serialize error: {}", e),
+            }
+            context.events.publish(event);
+        }
+        data.push(record);
+    }
+    if no_update_record {
+        context.database.insert_update(uuid, &update_record).await?;
+    } else {
+        context.database.refresh_update(uuid, &update_record).await?;
     }
+    Ok(update_record)
 }
 
-impl Serialize for ErrorWrapper<sqlx::Error> {
+/// `None` for the first name seen of a profile, which is not a change
+fn name_change_event(uuid: &Uuid, data: &NameHistory, record: &NameHistoryElement) -> Option<NameChangeEvent> {
+    let last = data.last()?;
+    Some(NameChangeEvent {
+        id: uuid.simple(),
+        old_name: last.name.clone(),
+        new_name: record.name.clone(),
+        changed_to_at: record.changed_to_at.as_ref().and_then(into_millis).unwrap_or_default(),
+        source: UPDATE_BY_PROFILE,
+    })
+}
+
+impl From<&FetchError> for ApiError {
 
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer 
-    {
-        let mut s = serializer.serialize_struct("ErrorWrapper", 2)?;
-        s.serialize_field("type", "database")?;
-        s.serialize_field("error", self.0.to_string().as_str())?;
-        s.end()
+    fn from(e: &FetchError) -> Self {
+        match e {
+            FetchError::Request(e) => ApiError::from(e),
+            FetchError::Database(e) => ApiError::Database(e.to_string()),
+        }
     }
 }
 
 
-// fn from_request_error_status(e: JsonRequesterError) -> Response<Body> {
-//     let body = match e {
-//         JsonRequesterError::StatusCode(s) => format!("{{\"type\":\"request\",\"status\":{}}}", s.as_u16()),
-//         JsonRequesterError::Hyper(e) => format!("{{\"type\":\"request\",\"message\":{:?}}}", e.to_string()),
-//         JsonRequesterError::Deserialize(e) => format!("{{\"type\":\"request\",\"message\":{:?}}}", e.to_string()),
-//     };
-//     let mut resp = Response::new(body.into());
-//     resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
-//     *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
-//     resp
-// }
+#[cfg(test)]
+mod test {
 
-// fn from_database_error(e: sqlx::Error) -> Response<Body> {
-//     let body = format!("{{\"type\":\"database\",\"message\":{:?}}}", e.to_string());
-//     let mut resp = Response::new(body.into());
-//     resp.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
-//     *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-//     resp
-// }
\ No newline at end of file
+    use super::*;
+
+    #[test]
+    fn first_name_is_not_a_change() {
+        let uuid = Uuid::from_u128(1);
+        let now = SystemTime::now();
+        let mut data = NameHistory::new();
+        let first = NameHistoryElement::new("name1".to_string(), now);
+        // nothing to deliver or publish for a profile seen the first time
+        assert!(name_change_event(&uuid, &data, &first).is_none());
+        data.push(first);
+        let event = name_change_event(&uuid, &data, &NameHistoryElement::new("name2".to_string(), now)).unwrap();
+        assert_eq!(event.old_name, "name1");
+        assert_eq!(event.new_name, "name2");
+    }
+}
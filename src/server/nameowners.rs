@@ -0,0 +1,95 @@
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyper::Response;
+use hyper::Body;
+use hyper::StatusCode;
+use serde::Deserialize;
+use uuid::Uuid;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::client::config::UseCacheConfig;
+use crate::storage::data::NameOwner;
+use crate::storage::data::Update;
+
+use super::Context;
+use super::error::ApiError;
+use super::namehistory::check_upstream;
+
+#[derive(Debug, Deserialize)]
+pub struct NameOwnersQuery {
+    /// milliseconds since the unix epoch
+    pub at: Option<u64>,
+}
+
+pub async fn handle_get_name_owners(name: String, query: NameOwnersQuery, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_get_name_owners_inner(name, query, context).await {
+        Ok(data) if data.is_empty() => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(data) => Ok(warp::reply::json(&data).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+
+async fn handle_get_name_owners_inner(name: String, query: NameOwnersQuery, context: Context) -> Result<Vec<NameOwner>, ApiError> {
+    let mut data = context.database.get_name_owners(name.as_str()).await?;
+    if let Some(at) = query.at {
+        let at = SystemTime::UNIX_EPOCH + Duration::from_millis(at);
+        data.retain(|owner| owner.held_at(&at));
+    }
+    // mojang only knows the current owner, so a query about the past can not fall back to it
+    if data.is_empty() && query.at.is_none() && is_valid_name(name.as_str()) {
+        check_upstream(&context)?;
+        let profile_id = context.requester.request_uuid(name.as_str()).await?;
+        tracing::debug!("request uuid of name {}: {:?}", &name, &profile_id);
+        if let Some(profile_id) = profile_id {
+            data.push(NameOwner { uuid: profile_id.id, name: profile_id.name, changed_to_at: None, changed_away_at: None });
+        }
+    }
+    Ok(data)
+}
+
+/// the uuid of the profile currently named `name`: the latest name in the database while it is within `use_cache`, else the username endpoint;
+/// `None` if nobody has it
+pub(crate) async fn resolve_name(name: &str, context: &Context) -> Result<Option<Uuid>, ApiError> {
+    let owners = context.database.get_name_owners(name).await?;
+    // of several profiles whose latest name it is, the most recent change wins; the others have changed since
+    if let Some(owner) = owners.iter().rev().find(|owner| owner.changed_away_at.is_none()) {
+        let update = context.database.get_update(&owner.uuid).await?;
+        if is_current_owner(update.as_ref(), &SystemTime::now(), context.use_cache_config.as_ref()) {
+            return Ok(Some(owner.uuid));
+        }
+    }
+    check_upstream(context)?;
+    let profile_id = context.requester.request_uuid(name).await?;
+    tracing::debug!("resolve name {}: {:?}", name, &profile_id);
+    Ok(profile_id.map(|profile_id| profile_id.id))
+}
+
+/// past `use_cache` the profile may have changed away from its cached latest name, and someone else registered it since
+fn is_current_owner(update: Option<&Update>, now: &SystemTime, use_cache_config: &UseCacheConfig) -> bool {
+    update.map(|update| !update.missing && update.use_cache(now, use_cache_config)).unwrap_or(false)
+}
+
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn current_owner() {
+        let config = UseCacheConfig::default();
+        let now = SystemTime::now();
+        assert!(is_current_owner(Some(&Update::new(now - Duration::from_secs(3600), true)), &now, &config));
+        // checked long ago: the name may have been released and registered again by another account
+        assert!(!is_current_owner(Some(&Update::new(now - Duration::from_secs(40 * 24 * 3600), true)), &now, &config));
+        assert!(!is_current_owner(Some(&Update::new_missing(now)), &now, &config));
+        assert!(!is_current_owner(None, &now, &config));
+    }
+}
@@ -0,0 +1,78 @@
+use hyper::Response;
+use hyper::Body;
+use serde::Deserialize;
+use serde::Serialize;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::storage::data::NameRecord;
+
+use super::Context;
+use super::error::ApiError;
+
+pub const SEARCH_DEFAULT_LIMIT: u32 = 50;
+pub const SEARCH_MAX_LIMIT: u32 = 500;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameSearchMode {
+    Prefix,
+    Substring,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NameSearchQuery {
+    pub q: String,
+    pub mode: Option<NameSearchMode>,
+    /// `next` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NameSearchPage {
+
+    pub names: Vec<NameRecord>,
+
+    /// opaque; the name and `index` of the last row
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+pub async fn handle_search_names(query: NameSearchQuery, context: Context) -> Result<Response<Body>, Rejection> {
+    match handle_search_names_inner(query, context).await {
+        Ok(data) => Ok(warp::reply::json(&data).into_response()),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+
+async fn handle_search_names_inner(query: NameSearchQuery, context: Context) -> Result<NameSearchPage, ApiError> {
+    let (cursor_index, cursor_name) = match query.cursor.as_deref() {
+        Some(cursor) => decode_cursor(cursor).ok_or_else(|| ApiError::InvalidRequest(format!("invalid cursor {:?}", cursor)))?,
+        None => (0, String::new()),
+    };
+    let limit = query.limit.unwrap_or(SEARCH_DEFAULT_LIMIT).clamp(1, SEARCH_MAX_LIMIT);
+    let names = match query.mode.unwrap_or(NameSearchMode::Prefix) {
+        NameSearchMode::Prefix => context.database.search_names_prefix(query.q.as_str(), cursor_name.as_str(), cursor_index, limit).await,
+        NameSearchMode::Substring => context.database.search_names_substring(query.q.as_str(), cursor_index, limit).await,
+    }?;
+    let next = if names.len() as u32 == limit {
+        names.last().map(encode_cursor)
+    } else {
+        None
+    };
+    Ok(NameSearchPage { names, next })
+}
+
+/// `<index>:<name>` in url-safe base64; the substring mode only needs the index
+fn encode_cursor(record: &NameRecord) -> String {
+    base64::encode_config(format!("{}:{}", record.index, record.name), base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(i64, String)> {
+    let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (index, name) = decoded.split_once(':')?;
+    Some((index.parse().ok()?, name.to_string()))
+}
@@ -0,0 +1,59 @@
+use hyper::Response;
+use hyper::Body;
+use utoipa::Modify;
+use utoipa::OpenApi;
+use utoipa::openapi::security::HttpAuthScheme;
+use utoipa::openapi::security::HttpBuilder;
+use utoipa::openapi::security::SecurityScheme;
+use warp::Reply;
+
+use crate::storage::data::NameHistoryElement;
+use crate::storage::data::Tombstone;
+
+use super::error::Problem;
+use super::format::Format;
+use super::namehistory;
+use super::v2;
+use super::v2::CacheInfo;
+use super::v2::NameHistoryEntry;
+use super::v2::NameHistoryV2;
+
+/// the OpenAPI 3 document, built from the handler annotations and the schemas of the types they serve
+#[derive(OpenApi)]
+#[openapi(
+    paths(namehistory::handle_get_name_history, v2::handle_get_name_history),
+    components(schemas(NameHistoryElement, Tombstone, Problem, Format, NameHistoryV2, NameHistoryEntry, CacheInfo)),
+    modifiers(&ApiKeyScheme),
+    tags((name = "names", description = "name histories of minecraft profiles")),
+)]
+pub struct ApiDoc;
+
+struct ApiKeyScheme;
+
+impl Modify for ApiKeyScheme {
+
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme("api_key", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        }
+    }
+}
+
+pub fn handle_get_openapi() -> Response<Body> {
+    warp::reply::json(&ApiDoc::openapi()).into_response()
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn document() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(doc["paths"]["/user/profiles/{uuid}/names"]["get"].is_object());
+        assert!(doc["components"]["schemas"]["NameHistoryElement"]["properties"]["changedToAt"].is_object());
+        assert!(doc["components"]["schemas"]["Problem"]["properties"]["code"].is_object());
+    }
+}
@@ -0,0 +1,181 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::HeaderMap;
+use hyper::header;
+use warp::Filter;
+use warp::Rejection;
+
+use crate::utils::ratelimit::RateLimiter;
+
+use super::config::RateLimitConfig;
+use super::error::ApiError;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// who a request is accounted to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    Ip(IpAddr),
+    /// the `index` of an api key
+    ApiKey(i64),
+}
+
+impl ClientKey {
+
+    pub fn from_remote(remote: Option<SocketAddr>) -> Self {
+        Self::Ip(remote.map(|addr| addr.ip()).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))
+    }
+}
+
+impl Default for ClientKey {
+
+    fn default() -> Self {
+        Self::from_remote(None)
+    }
+}
+
+impl fmt::Display for ClientKey {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{}", ip),
+            Self::ApiKey(index) => write!(f, "key#{}", index),
+        }
+    }
+}
+
+/// the peer address, or the client named by the forwarding headers if the peer is one of `trusted_proxies`
+pub fn client_key(trusted_proxies: Arc<Vec<IpAddr>>) -> impl Filter<Extract = (ClientKey, ), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::header::headers_cloned())
+        .map(move |remote: Option<SocketAddr>, headers: HeaderMap| match remote {
+            Some(remote) if trusted_proxies.contains(&remote.ip()) => ClientKey::Ip(forwarded_client(remote.ip(), &headers, &trusted_proxies)),
+            remote => ClientKey::from_remote(remote),
+        })
+}
+
+/// walks the hops from the nearest one and stops at the first address that is not a trusted proxy; the headers can be forged
+/// by the client, so everything left of that is ignored. `Forwarded` wins over `X-Forwarded-For`
+fn forwarded_client(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let forwarded = join_header(headers, header::FORWARDED);
+    let hops = if !forwarded.is_empty() {
+        forwarded.split(',')
+            .map(|element| element.split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_node(node)))
+            .collect::<Vec<_>>()
+    } else {
+        let x_forwarded_for = join_header(headers, X_FORWARDED_FOR);
+        if x_forwarded_for.is_empty() {
+            return peer;
+        }
+        x_forwarded_for.split(',').map(parse_node).collect::<Vec<_>>()
+    };
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            // an obfuscated or unknown hop, the last trusted one is as far as it goes
+            None => break,
+        }
+    }
+    client
+}
+
+/// all lines of a header, the way a single comma separated line reads
+fn join_header(headers: &HeaderMap, name: impl header::AsHeaderName) -> String {
+    headers.get_all(name).iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `1.2.3.4`, `1.2.3.4:80`, `"[::1]:80"` or `"[::1]"`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>().ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| node.strip_prefix('[')?.strip_suffix(']')?.parse().ok())
+}
+
+
+/// two budgets per client: `read` is spent by every request, `upstream` only by requests that reach Mojang
+pub struct ClientRateLimiter {
+    enabled: bool,
+    read: RateLimiter<ClientKey>,
+    upstream: RateLimiter<ClientKey>,
+}
+
+impl ClientRateLimiter {
+
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            read: RateLimiter::new(config.read_burst, config.read_period),
+            upstream: RateLimiter::new(config.upstream_burst, config.upstream_period),
+        }
+    }
+
+    pub fn check_read(&self, client: &ClientKey) -> Result<(), Duration> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.read.check(client.clone())
+    }
+
+    pub fn check_upstream(&self, client: &ClientKey) -> Result<(), Duration> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.upstream.check(client.clone())
+    }
+}
+
+
+/// rejects with `ApiError::RateLimited` once the client's read budget is spent
+pub fn read_limit(limiter: Arc<ClientRateLimiter>, trusted_proxies: Arc<Vec<IpAddr>>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    client_key(trusted_proxies)
+        .and_then(move |client: ClientKey| {
+            let r = limiter.check_read(&client).map_err(|wait| {
+                tracing::debug!("read limit hit by {}", &client);
+                warp::reject::custom(ApiError::RateLimited(wait))
+            });
+            async move { r }
+        })
+        .untuple_one()
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn forwarded() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let trusted = vec![proxy, "10.0.0.2".parse().unwrap()];
+        let client = |name: &'static str, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, header::HeaderValue::from_static(value));
+            forwarded_client(proxy, &headers, &trusted).to_string()
+        };
+        assert_eq!(forwarded_client(proxy, &HeaderMap::new(), &trusted).to_string(), "10.0.0.1");
+        // the leftmost entries are whatever the client sent
+        assert_eq!(client("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.2"), "1.2.3.4");
+        assert_eq!(client("forwarded", "for=6.6.6.6, for=\"[2001:db8::1]:4711\";proto=https"), "2001:db8::1");
+        assert_eq!(client("forwarded", "for=1.2.3.4, for=_hidden"), "10.0.0.1");
+    }
+}
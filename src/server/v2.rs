@@ -0,0 +1,179 @@
+use std::time::SystemTime;
+
+use chrono::DateTime;
+use chrono::SecondsFormat;
+use chrono::Utc;
+use hyper::HeaderMap;
+use hyper::Response;
+use hyper::Body;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use warp::Rejection;
+use warp::Reply;
+
+use crate::storage::data::NameRecord;
+use crate::storage::data::into_millis;
+
+use super::Context;
+use super::error::ApiError;
+use super::format::Format;
+use super::nameat::is_exact;
+use super::namehistory::NameHistoryLookup;
+use super::namehistory::NameHistoryQuery;
+use super::namehistory::ProfileRef;
+use super::namehistory::check_force;
+use super::namehistory::handle_get_name_history_inner;
+use super::namehistory::reply_cacheable;
+
+/// a name history with the provenance of every name and the state of the cache it was served from
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NameHistoryV2 {
+
+    /// the uuid without hyphens
+    pub id: String,
+
+    /// the first known name first
+    pub names: Vec<NameHistoryEntry>,
+
+    pub cache: CacheInfo,
+}
+
+/// one name a profile has had, with where it came from
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NameHistoryEntry {
+
+    /// the row of the name, as taken by the admin routes
+    pub index: i64,
+
+    pub name: String,
+
+    /// milliseconds since the unix epoch at which the profile changed to this name; absent for the first known name
+    #[serde(rename = "changedToAt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<u64>)]
+    pub changed_to_at: Option<u128>,
+
+    /// `changedToAt` as ISO-8601
+    #[serde(rename = "changedToAtIso")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_to_at_iso: Option<String>,
+
+    /// 1 if the service saw the name on the profile endpoint, 2 if an admin entered it
+    pub source: u32,
+
+    /// false if `changedToAt` is only the time the service observed the change, which happened between that and the previous check
+    pub exact: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheInfo {
+
+    /// milliseconds since the unix epoch of the last check against upstream
+    #[serde(rename = "updatedAt")]
+    #[schema(value_type = u64)]
+    pub updated_at: u128,
+
+    #[serde(rename = "updatedAtIso")]
+    pub updated_at_iso: String,
+
+    /// the name changed at the last check
+    pub changed: bool,
+
+    /// seconds left until the next check, same as `Cache-Control: max-age`
+    #[serde(rename = "maxAge")]
+    pub max_age: u64,
+
+    /// served past its TTL, while it is refreshed in the background or because upstream failed
+    pub stale: bool,
+}
+
+/// same as `/user/profiles/{uuid}/names`, in the richer v2 format
+#[utoipa::path(
+    get,
+    path = "/v2/user/profiles/{uuid}/names",
+    tag = "names",
+    params(
+        ("uuid" = String, Path, description = "uuid of the profile, with or without hyphens, or its current name"),
+        NameHistoryQuery,
+        ("key" = Option<String>, Query, description = "api key, instead of `Authorization: Bearer <key>`"),
+        ("Cache-Control" = Option<String>, Header, description = "`no-cache` skips the `use_cache` check, same as `refresh=true`"),
+    ),
+    responses(
+        (status = 200, description = "name history; the line based formats only have `names`", content(
+            ("application/json" = NameHistoryV2),
+            ("text/csv" = String),
+            ("application/x-ndjson" = String),
+            ("application/msgpack" = NameHistoryV2),
+        )),
+        (status = 304, description = "the history matches `If-None-Match` or `If-Modified-Since`"),
+        (status = "4XX", description = "see `/user/profiles/{uuid}/names`", body = Problem, content_type = "application/problem+json"),
+        (status = "5XX", description = "see `/user/profiles/{uuid}/names`", body = Problem, content_type = "application/problem+json"),
+    ),
+    security((), ("api_key" = [])),
+)]
+pub async fn handle_get_name_history(profile: ProfileRef, query: NameHistoryQuery, cache_control: Option<String>, headers: HeaderMap, context: Context) -> Result<Response<Body>, Rejection> {
+    let uuid = match profile.resolve(&context).await {
+        Ok(uuid) => uuid,
+        Err(e) => return Ok(e.into_response()),
+    };
+    let force = match check_force(&uuid, &query, cache_control.as_deref(), &context).await {
+        Ok(force) => force,
+        Err(e) => return Ok(e.into_response()),
+    };
+    match handle_get_name_history_inner_v2(uuid, force, context.clone()).await {
+        Ok((data, lookup)) => Ok(reply_cacheable(&data, &lookup, Format::negotiate(query.format, &headers), &headers, &context)),
+        Err(e) => Ok(e.into_response())
+    }
+}
+
+
+async fn handle_get_name_history_inner_v2(uuid: Uuid, force: bool, context: Context) -> Result<(NameHistoryV2, NameHistoryLookup), ApiError> {
+    let lookup = handle_get_name_history_inner(uuid, force, context.clone()).await?;
+    let records = context.database.get_name_records(&uuid).await?;
+    let update = &lookup.update;
+    let cache = CacheInfo {
+        updated_at: into_millis(&update.update).unwrap_or_default(),
+        updated_at_iso: into_iso8601(&update.update),
+        changed: update.changed,
+        max_age: update.expires_in(&SystemTime::now(), context.use_cache_config.as_ref()).as_secs(),
+        stale: lookup.stale,
+    };
+    let data = NameHistoryV2 {
+        id: uuid.simple().to_string(),
+        names: records.iter().map(into_entry).collect(),
+        cache,
+    };
+    Ok((data, lookup))
+}
+
+fn into_entry(record: &NameRecord) -> NameHistoryEntry {
+    NameHistoryEntry {
+        index: record.index,
+        name: record.name.clone(),
+        changed_to_at: record.changed_to_at.as_ref().and_then(into_millis),
+        changed_to_at_iso: record.changed_to_at.as_ref().map(into_iso8601),
+        source: record.source,
+        exact: is_exact(record),
+    }
+}
+
+/// UTC with milliseconds, like `2022-10-01T12:00:00.000Z`
+fn into_iso8601(v: &SystemTime) -> String {
+    DateTime::<Utc>::from(*v).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn iso8601() {
+        let t = SystemTime::UNIX_EPOCH + Duration::from_millis(1_664_625_600_123);
+        assert_eq!(into_iso8601(&t), "2022-10-01T12:00:00.123Z");
+    }
+}
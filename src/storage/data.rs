@@ -9,6 +9,7 @@ use serde::ser::SerializeStruct;
 use sqlx::FromRow;
 use sqlx::Row;
 use sqlx::sqlite::SqliteRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::client::config::UseCacheConfig; 
@@ -33,10 +34,40 @@ fn systemtime_error(index: &str, raw: i64) -> sqlx::Error {
 }
 
 
+/// reads a page of a real table, so a corrupt or unreadable file fails it, unlike a bare `SELECT 1`
+pub(super) const PING: &'static str = "SELECT 1 FROM `updates` LIMIT 1";
+
+
 pub(super) fn into_argument_uuid<'a>(v: &'a Uuid) -> &'a [u8] {
     v.as_bytes().as_slice()
 }
 
+pub(super) fn from_column_uuid(row: &SqliteRow, index: &str) -> Result<Uuid, sqlx::Error> {
+    let raw: Vec<u8> = row.try_get(index)?;
+    Uuid::from_slice(raw.as_slice()).map_err(|e| sqlx::Error::ColumnDecode { index: index.to_string(), source: Box::new(e) })
+}
+
+fn from_column_millis(row: &SqliteRow, index: &str) -> Result<Option<SystemTime>, sqlx::Error> {
+    let timestamp: Option<i64> = row.try_get(index)?;
+    if let Some(timestamp) = timestamp {
+        let v: u64 = unsafe { std::mem::transmute(timestamp) };
+        let d = Duration::from_millis(v);
+        Ok(Some(SystemTime::UNIX_EPOCH.checked_add(d).ok_or_else(|| systemtime_error(index, timestamp))?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn placeholders(n: usize) -> String {
+    let mut s = "?,".repeat(n);
+    s.pop();
+    s
+}
+
+pub(crate) fn into_millis(v: &SystemTime) -> Option<u128> {
+    v.duration_since(SystemTime::UNIX_EPOCH).ok().map(|d| d.as_millis())
+}
+
 pub(super) const CREATE_TABLE_NAMES: &'static str = 
 "CREATE TABLE IF NOT EXISTS `names` (
     \"index\"	INTEGER NOT NULL UNIQUE,
@@ -51,6 +82,43 @@ pub(super) const CREATE_TABLE_NAMES: &'static str =
 pub(super) const CREATE_INDEX_NAMES: &'static str =
 "CREATE INDEX IF NOT EXISTS `names_index_uuid` ON `names`(\"uuid\")";
 
+pub(super) const CREATE_INDEX_NAMES_NAME: &'static str =
+"CREATE INDEX IF NOT EXISTS `names_index_name` ON `names`(\"name\" COLLATE NOCASE)";
+
+pub(super) const QUERY_NAMES_FTS_EXISTS: &'static str =
+"SELECT COUNT(*) FROM `sqlite_master` WHERE \"type\" = 'table' AND \"name\" = 'names_fts'";
+
+pub(super) const CREATE_TABLE_NAMES_FTS: &'static str =
+"CREATE VIRTUAL TABLE IF NOT EXISTS `names_fts` USING fts5(
+    \"name\",
+    content = 'names',
+    content_rowid = 'index',
+    tokenize = 'trigram'
+)
+";
+
+pub(super) const REBUILD_NAMES_FTS: &'static str =
+"INSERT INTO `names_fts`(`names_fts`) VALUES('rebuild')";
+
+pub(super) const CREATE_TRIGGER_NAMES_FTS_INSERT: &'static str =
+"CREATE TRIGGER IF NOT EXISTS `names_fts_insert` AFTER INSERT ON `names` BEGIN
+    INSERT INTO `names_fts`(rowid, \"name\") VALUES (new.\"index\", new.\"name\");
+END
+";
+
+pub(super) const CREATE_TRIGGER_NAMES_FTS_DELETE: &'static str =
+"CREATE TRIGGER IF NOT EXISTS `names_fts_delete` AFTER DELETE ON `names` BEGIN
+    INSERT INTO `names_fts`(`names_fts`, rowid, \"name\") VALUES ('delete', old.\"index\", old.\"name\");
+END
+";
+
+pub(super) const CREATE_TRIGGER_NAMES_FTS_UPDATE: &'static str =
+"CREATE TRIGGER IF NOT EXISTS `names_fts_update` AFTER UPDATE OF \"name\" ON `names` BEGIN
+    INSERT INTO `names_fts`(`names_fts`, rowid, \"name\") VALUES ('delete', old.\"index\", old.\"name\");
+    INSERT INTO `names_fts`(rowid, \"name\") VALUES (new.\"index\", new.\"name\");
+END
+";
+
 pub(super) const QUERY_NAME_HISTORY: &'static str = 
 "SELECT \"name\", \"changedToAt\"
 FROM `names`
@@ -58,6 +126,22 @@ WHERE \"uuid\" = ?
 ORDER BY \"changedToAt\"
 ";
 
+pub(super) const QUERY_NAME_RECORDS: &'static str = 
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"uuid\" = ?
+ORDER BY \"changedToAt\", \"index\"
+";
+
+pub(super) fn query_name_histories(n: usize) -> String {
+    format!(
+"SELECT \"uuid\", \"name\", \"changedToAt\"
+FROM `names`
+WHERE \"uuid\" IN ({})
+ORDER BY \"changedToAt\"
+", placeholders(n))
+}
+
 pub(super) const INSERT_NAME: &'static str = 
 "INSERT INTO `names`
 (\"uuid\", \"name\", \"changedToAt\", \"source\")
@@ -74,11 +158,16 @@ VALUES (?, ?, ?)
 pub type NameHistory = Vec<NameHistoryElement>;
 
 
-#[derive(Debug)]
+/// one name a profile has had
+#[derive(Debug, Clone, ToSchema)]
 pub struct NameHistoryElement {
     
     pub name: String,
     
+    /// milliseconds since the unix epoch at which the profile changed to this name; absent for the first known name.
+    /// A name the service picked up from the profile endpoint has the time the change was first observed here,
+    /// the change itself happened between that and the previous check
+    #[schema(rename = "changedToAt", value_type = Option<u64>)]
     pub changed_to_at: Option<SystemTime>,
 }
 
@@ -148,42 +237,268 @@ impl NameHistoryElement {
 }
 
 
+/// pages through the `names_index_name` index, ordered by name then `index`; the cursor is the name and `index` of the last row,
+/// so it still works once that row is renamed or deleted
+pub(super) const SEARCH_NAMES_PREFIX: &'static str =
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"name\" LIKE ?1 ESCAPE '\\'
+AND \"name\" >= ?2 COLLATE NOCASE
+AND (\"name\" COLLATE NOCASE, \"index\") > (?2, ?3)
+ORDER BY \"name\" COLLATE NOCASE, \"index\"
+LIMIT ?4
+";
+
+/// trigram match, only usable with at least 3 characters; ordered by `index`
+pub(super) const SEARCH_NAMES_SUBSTRING: &'static str =
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"index\" IN (
+    SELECT rowid
+    FROM `names_fts`
+    WHERE `names_fts` MATCH ? AND rowid > ?
+    ORDER BY rowid
+    LIMIT ?
+)
+ORDER BY \"index\"
+";
+
+pub(super) const SEARCH_NAMES_SUBSTRING_SHORT: &'static str =
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"name\" LIKE ? ESCAPE '\\' AND \"index\" > ?
+ORDER BY \"index\"
+LIMIT ?
+";
+
+pub(super) const QUERY_NAME_RECORD: &'static str =
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"index\" = ?
+";
+
+pub(super) const UPDATE_NAME_RECORD: &'static str =
+"UPDATE `names`
+SET \"name\" = ?, \"changedToAt\" = ?, \"source\" = ?
+WHERE \"index\" = ?
+";
+
+pub(super) const DELETE_NAME_RECORD: &'static str =
+"DELETE FROM `names`
+WHERE \"index\" = ?
+";
+
+pub(super) const QUERY_NAME_CHANGES: &'static str =
+"SELECT \"index\", \"uuid\", \"name\", \"changedToAt\", \"source\"
+FROM `names`
+WHERE \"index\" > ?
+ORDER BY \"index\"
+LIMIT ?
+";
+
+pub(super) fn escape_like(v: &str) -> String {
+    let mut s = String::with_capacity(v.len());
+    for c in v.chars() {
+        if c == '\\' || c == '%' || c == '_' {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s
+}
+
+pub(super) fn quote_fts_phrase(v: &str) -> String {
+    format!("\"{}\"", v.replace('"', "\"\""))
+}
+
+
+/// a raw row of the `names` table
+#[derive(Debug)]
+pub struct NameRecord {
+
+    pub index: i64,
+
+    pub uuid: Uuid,
+
+    pub name: String,
+
+    pub changed_to_at: Option<SystemTime>,
+
+    pub source: u32,
+}
+
+impl Serialize for NameRecord {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer 
+    {
+        let mut s = serializer.serialize_struct("NameRecord", 5)?;
+        s.serialize_field("index", &self.index)?;
+        s.serialize_field("id", &self.uuid.simple())?;
+        s.serialize_field("name", self.name.as_str())?;
+        if let Some(t) = self.changed_to_at.as_ref().and_then(into_millis) {
+            s.serialize_field("changedToAt", &t)?;
+        } else {
+            s.skip_field("changedToAt")?;
+        }
+        s.serialize_field("source", &self.source)?;
+        s.end()
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for NameRecord {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let index = row.try_get("index")?;
+        let uuid = from_column_uuid(row, "uuid")?;
+        let name = row.try_get("name")?;
+        let changed_to_at = from_column_millis(row, "changedToAt")?;
+        let source = row.try_get("source")?;
+        Ok(NameRecord { index, uuid, name, changed_to_at, source })
+    }
+}
+
+
+pub(super) const QUERY_NAME_OWNERS: &'static str =
+"SELECT n.\"uuid\", n.\"name\", n.\"changedToAt\", (
+    SELECT MIN(m.\"changedToAt\")
+    FROM `names` m
+    WHERE m.\"uuid\" = n.\"uuid\" AND m.\"changedToAt\" > IFNULL(n.\"changedToAt\", -1)
+) AS \"changedAwayAt\"
+FROM `names` n
+WHERE n.\"name\" = ? COLLATE NOCASE
+ORDER BY n.\"changedToAt\"
+";
+
+
+/// one period during which `uuid` held `name`; `changed_away_at` is `None` while it is still the latest name
+#[derive(Debug)]
+pub struct NameOwner {
+
+    pub uuid: Uuid,
+
+    pub name: String,
+
+    pub changed_to_at: Option<SystemTime>,
+
+    pub changed_away_at: Option<SystemTime>,
+}
+
+impl Serialize for NameOwner {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer 
+    {
+        let mut s = serializer.serialize_struct("NameOwner", 4)?;
+        s.serialize_field("id", &self.uuid.simple())?;
+        s.serialize_field("name", self.name.as_str())?;
+        if let Some(t) = self.changed_to_at.as_ref().and_then(into_millis) {
+            s.serialize_field("changedToAt", &t)?;
+        } else {
+            s.skip_field("changedToAt")?;
+        }
+        if let Some(t) = self.changed_away_at.as_ref().and_then(into_millis) {
+            s.serialize_field("changedAwayAt", &t)?;
+        } else {
+            s.skip_field("changedAwayAt")?;
+        }
+        s.end()
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for NameOwner {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let uuid = from_column_uuid(row, "uuid")?;
+        let name = row.try_get("name")?;
+        let changed_to_at = from_column_millis(row, "changedToAt")?;
+        let changed_away_at = from_column_millis(row, "changedAwayAt")?;
+        Ok(NameOwner { uuid, name, changed_to_at, changed_away_at })
+    }
+}
+
+impl NameOwner {
+
+    pub fn held_at(&self, at: &SystemTime) -> bool {
+        let since = self.changed_to_at.as_ref().map(|t| t <= at).unwrap_or(true);
+        let until = self.changed_away_at.as_ref().map(|t| at < t).unwrap_or(true);
+        since && until
+    }
+}
+
+
 pub(super) const CREATE_TABLE_UPDATES: &'static str =
 "CREATE TABLE IF NOT EXISTS `updates` (
     \"uuid\"	BLOB NOT NULL UNIQUE,
     \"update\"	INTEGER NOT NULL,
     \"changed\"	BOOLEAN NOT NULL,
+    \"missing\"	BOOLEAN NOT NULL DEFAULT 0,
     PRIMARY KEY(\"uuid\")
 )
 ";
 
+pub(super) const QUERY_UPDATES_MISSING_EXISTS: &'static str =
+"SELECT COUNT(*) FROM pragma_table_info('updates') WHERE \"name\" = 'missing'";
+
+/// for databases created before `missing`
+pub(super) const ADD_COLUMN_UPDATES_MISSING: &'static str =
+"ALTER TABLE `updates` ADD COLUMN \"missing\" BOOLEAN NOT NULL DEFAULT 0";
+
 pub(super) const CREATE_INDEX_UPDATES: &'static str =
 "CREATE INDEX IF NOT EXISTS `updates_index_uuid` ON `updates`(\"uuid\")";
 
 pub(super) const QUERY_UPDATE: &'static str =
-"SELECT \"update\", \"changed\"
+"SELECT \"update\", \"changed\", \"missing\"
 FROM `updates`
 WHERE \"uuid\" = ?
 ";
 
+pub(super) fn query_updates(n: usize) -> String {
+    format!(
+"SELECT \"uuid\", \"update\", \"changed\", \"missing\"
+FROM `updates`
+WHERE \"uuid\" IN ({})
+", placeholders(n))
+}
+
 pub(super) const REFRESH_UPDATE: &'static str =
 "UPDATE `updates`
-SET \"update\" = ?, \"changed\" = ?
+SET \"update\" = ?, \"changed\" = ?, \"missing\" = ?
+WHERE \"uuid\" = ?
+";
+
+pub(super) const DELETE_UPDATE: &'static str =
+"DELETE FROM `updates`
 WHERE \"uuid\" = ?
 ";
 
 pub(super) const NEW_UPDATE: &'static str =
 "INSERT INTO `updates`
-VALUES(?, ?, ?)
+(\"uuid\", \"update\", \"changed\", \"missing\")
+VALUES(?, ?, ?, ?)
 ";
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Update {
 
     pub update: SystemTime,
 
-    pub changed: bool
+    pub changed: bool,
+
+    /// upstream had no such profile at `update`
+    pub missing: bool,
+}
+
+impl Serialize for Update {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer 
+    {
+        let mut s = serializer.serialize_struct("Update", 3)?;
+        s.serialize_field("update", &into_millis(&self.update).unwrap_or_default())?;
+        s.serialize_field("changed", &self.changed)?;
+        s.serialize_field("missing", &self.missing)?;
+        s.end()
+    }
 }
 
 impl<'r> FromRow<'r, SqliteRow> for Update {
@@ -195,7 +510,8 @@ impl<'r> FromRow<'r, SqliteRow> for Update {
             SystemTime::UNIX_EPOCH.checked_add(d).ok_or_else(|| systemtime_error("update", update_timestamp))?
         };
         let changed = row.try_get("changed")?;
-        Ok(Update { update, changed })
+        let missing = row.try_get("missing")?;
+        Ok(Update { update, changed, missing })
     }
 }
 
@@ -204,18 +520,33 @@ impl Update {
     pub fn new(update: SystemTime, changed: bool) -> Self {
         Self {
             update,
-            changed: true
+            changed: true,
+            missing: false,
+        }
+    }
+
+    pub fn new_missing(update: SystemTime) -> Self {
+        Self {
+            update,
+            changed: false,
+            missing: true,
+        }
+    }
+
+    fn ttl(&self, config: &UseCacheConfig) -> Duration {
+        if self.missing {
+            config.missing
+        } else if self.changed {
+            config.changed
+        } else {
+            config.unchanged
         }
     }
 
     pub fn use_cache(&self, now: &SystemTime, config: &UseCacheConfig) -> bool {
         match now.duration_since(self.update) {
             Ok(d) => {
-                if self.changed {
-                    d < config.changed
-                } else {
-                    d < config.unchanged
-                }
+                d < self.ttl(config)
             },
             Err(_e) => {
                 false
@@ -223,6 +554,24 @@ impl Update {
         }
     }
 
+    /// past `use_cache`, but still within `stale_while_revalidate` of it
+    pub fn use_cache_while_revalidate(&self, now: &SystemTime, config: &UseCacheConfig) -> bool {
+        let ttl = self.ttl(config);
+        match now.duration_since(self.update) {
+            Ok(d) => d >= ttl && d < ttl.saturating_add(config.stale_while_revalidate),
+            Err(_e) => false,
+        }
+    }
+
+    /// how long this record may still be served from the cache
+    pub fn expires_in(&self, now: &SystemTime, config: &UseCacheConfig) -> Duration {
+        let ttl = self.ttl(config);
+        match now.duration_since(self.update) {
+            Ok(d) => ttl.saturating_sub(d),
+            Err(_e) => Duration::ZERO,
+        }
+    }
+
     pub(super) fn into_argument_systemtime<'a>(v: &'a SystemTime) -> i64 {
         match v.duration_since(SystemTime::UNIX_EPOCH) {
             Ok(s) => {
@@ -239,3 +588,275 @@ impl Update {
 }
 
 
+
+pub(super) const CREATE_TABLE_WEBHOOK_DELIVERIES: &'static str =
+"CREATE TABLE IF NOT EXISTS `webhook_deliveries` (
+    \"index\"	INTEGER NOT NULL UNIQUE,
+    \"target\"	TEXT NOT NULL,
+    \"payload\"	TEXT NOT NULL,
+    \"attempts\"	INTEGER NOT NULL DEFAULT 0,
+    \"nextAttemptAt\"	INTEGER NOT NULL,
+    PRIMARY KEY(\"index\" AUTOINCREMENT)
+)
+";
+
+pub(super) const CREATE_INDEX_WEBHOOK_DELIVERIES: &'static str =
+"CREATE INDEX IF NOT EXISTS `webhook_deliveries_index_next` ON `webhook_deliveries`(\"nextAttemptAt\")";
+
+pub(super) const INSERT_WEBHOOK_DELIVERY: &'static str =
+"INSERT INTO `webhook_deliveries`
+(\"target\", \"payload\", \"nextAttemptAt\")
+VALUES (?, ?, ?)
+";
+
+pub(super) const QUERY_DUE_WEBHOOK_DELIVERIES: &'static str =
+"SELECT \"index\", \"target\", \"payload\", \"attempts\"
+FROM `webhook_deliveries`
+WHERE \"nextAttemptAt\" <= ?
+ORDER BY \"nextAttemptAt\", \"index\"
+LIMIT ?
+";
+
+pub(super) const RESCHEDULE_WEBHOOK_DELIVERY: &'static str =
+"UPDATE `webhook_deliveries`
+SET \"attempts\" = ?, \"nextAttemptAt\" = ?
+WHERE \"index\" = ?
+";
+
+pub(super) const DELETE_WEBHOOK_DELIVERY: &'static str =
+"DELETE FROM `webhook_deliveries`
+WHERE \"index\" = ?
+";
+
+
+/// a pending webhook POST; `target` is the name of the configured target, its url and secret are looked up from the config when sending
+#[derive(Debug, FromRow)]
+pub struct WebhookDelivery {
+
+    pub index: i64,
+
+    pub target: String,
+
+    pub payload: String,
+
+    pub attempts: u32,
+}
+
+
+pub(super) const CREATE_TABLE_AUDIT: &'static str =
+"CREATE TABLE IF NOT EXISTS `audit` (
+    \"index\"	INTEGER NOT NULL UNIQUE,
+    \"actor\"	TEXT NOT NULL,
+    \"action\"	TEXT NOT NULL,
+    \"uuid\"	BLOB NOT NULL,
+    \"before\"	TEXT,
+    \"after\"	TEXT,
+    \"time\"	INTEGER NOT NULL,
+    PRIMARY KEY(\"index\" AUTOINCREMENT)
+)
+";
+
+pub(super) const INSERT_AUDIT: &'static str =
+"INSERT INTO `audit`
+(\"actor\", \"action\", \"uuid\", \"before\", \"after\", \"time\")
+VALUES (?, ?, ?, ?, ?, ?)
+";
+
+pub(super) fn into_audit_value<T: Serialize>(v: &T) -> Result<String, sqlx::Error> {
+    serde_json::to_string(v).map_err(|e| sqlx::Error::Protocol(format!("audit value: {}", e)))
+}
+
+
+pub const PERMISSION_READ: u32 = 1;
+pub const PERMISSION_REFRESH: u32 = 2;
+pub const PERMISSION_ADMIN: u32 = 4;
+
+pub const PERMISSION_NAMES: [(u32, &'static str); 3] = [
+    (PERMISSION_READ, "read"),
+    (PERMISSION_REFRESH, "refresh"),
+    (PERMISSION_ADMIN, "admin"),
+];
+
+pub(super) const CREATE_TABLE_API_KEYS: &'static str =
+"CREATE TABLE IF NOT EXISTS `api_keys` (
+    \"index\"	INTEGER NOT NULL UNIQUE,
+    \"name\"	TEXT NOT NULL,
+    \"keyHash\"	TEXT NOT NULL UNIQUE,
+    \"permissions\"	INTEGER NOT NULL,
+    \"dailyQuota\"	INTEGER,
+    \"usageDay\"	INTEGER NOT NULL DEFAULT 0,
+    \"usage\"	INTEGER NOT NULL DEFAULT 0,
+    \"createdAt\"	INTEGER NOT NULL,
+    PRIMARY KEY(\"index\" AUTOINCREMENT)
+)
+";
+
+pub(super) const INSERT_API_KEY: &'static str =
+"INSERT INTO `api_keys`
+(\"name\", \"keyHash\", \"permissions\", \"dailyQuota\", \"createdAt\")
+VALUES (?, ?, ?, ?, ?)
+";
+
+pub(super) const QUERY_API_KEYS: &'static str =
+"SELECT \"index\", \"name\", \"permissions\", \"dailyQuota\", \"usageDay\", \"usage\", \"createdAt\"
+FROM `api_keys`
+ORDER BY \"index\"
+";
+
+pub(super) const QUERY_API_KEY: &'static str =
+"SELECT \"index\", \"name\", \"permissions\", \"dailyQuota\", \"usageDay\", \"usage\", \"createdAt\"
+FROM `api_keys`
+WHERE \"index\" = ?
+";
+
+pub(super) const QUERY_API_KEY_BY_HASH: &'static str =
+"SELECT \"index\", \"name\", \"permissions\", \"dailyQuota\", \"usageDay\", \"usage\", \"createdAt\"
+FROM `api_keys`
+WHERE \"keyHash\" = ?
+";
+
+/// counts one use against the quota of ?2 (days since the unix epoch); no row comes back if the key is unknown,
+/// lacks the permissions ?3 or has no quota left
+pub(super) const USE_API_KEY: &'static str =
+"UPDATE `api_keys`
+SET \"usage\" = CASE WHEN \"usageDay\" = ?2 THEN \"usage\" + 1 ELSE 1 END, \"usageDay\" = ?2
+WHERE \"keyHash\" = ?1
+    AND \"permissions\" & ?3 = ?3
+    AND (\"usageDay\" != ?2 OR \"dailyQuota\" IS NULL OR \"usage\" < \"dailyQuota\")
+RETURNING \"index\", \"name\", \"permissions\", \"dailyQuota\", \"usageDay\", \"usage\", \"createdAt\"
+";
+
+pub(super) const DELETE_API_KEY: &'static str =
+"DELETE FROM `api_keys`
+WHERE \"index\" = ?
+";
+
+
+/// a row of the `api_keys` table; the key itself is only stored as its sha256
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+
+    pub index: i64,
+
+    pub name: String,
+
+    pub permissions: u32,
+
+    /// `None` for no limit
+    pub daily_quota: Option<u32>,
+
+    /// days since the unix epoch of the last use
+    pub usage_day: i64,
+
+    /// uses during `usage_day`
+    pub usage: u32,
+
+    pub created_at: SystemTime,
+}
+
+impl ApiKey {
+
+    pub fn allows(&self, permissions: u32) -> bool {
+        self.permissions & permissions == permissions
+    }
+}
+
+impl Serialize for ApiKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer 
+    {
+        let permissions = PERMISSION_NAMES.iter()
+            .filter(|(p, _)| self.allows(*p))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        let mut s = serializer.serialize_struct("ApiKey", 7)?;
+        s.serialize_field("index", &self.index)?;
+        s.serialize_field("name", self.name.as_str())?;
+        s.serialize_field("permissions", &permissions)?;
+        s.serialize_field("dailyQuota", &self.daily_quota)?;
+        s.serialize_field("usageDay", &self.usage_day)?;
+        s.serialize_field("usage", &self.usage)?;
+        s.serialize_field("createdAt", &into_millis(&self.created_at).unwrap_or_default())?;
+        s.end()
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for ApiKey {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let index = row.try_get("index")?;
+        let name = row.try_get("name")?;
+        let permissions = row.try_get("permissions")?;
+        let daily_quota = row.try_get("dailyQuota")?;
+        let usage_day = row.try_get("usageDay")?;
+        let usage = row.try_get("usage")?;
+        let created_at = from_column_millis(row, "createdAt")?.unwrap_or(SystemTime::UNIX_EPOCH);
+        Ok(ApiKey { index, name, permissions, daily_quota, usage_day, usage, created_at })
+    }
+}
+
+
+pub(super) const CREATE_TABLE_TOMBSTONES: &'static str =
+"CREATE TABLE IF NOT EXISTS `tombstones` (
+    \"uuid\"	BLOB NOT NULL UNIQUE,
+    \"lastName\"	TEXT,
+    \"removedAt\"	INTEGER NOT NULL,
+    PRIMARY KEY(\"uuid\")
+)
+";
+
+pub(super) const INSERT_TOMBSTONE: &'static str =
+"INSERT OR REPLACE INTO `tombstones`
+(\"uuid\", \"lastName\", \"removedAt\")
+VALUES (?, ?, ?)
+";
+
+pub(super) const QUERY_TOMBSTONE: &'static str =
+"SELECT \"uuid\", \"lastName\", \"removedAt\"
+FROM `tombstones`
+WHERE \"uuid\" = ?
+";
+
+pub(super) const DELETE_TOMBSTONE: &'static str =
+"DELETE FROM `tombstones`
+WHERE \"uuid\" = ?
+";
+
+
+/// an account that upstream stopped knowing about, with the last name it was seen with
+#[derive(Debug, Clone, ToSchema)]
+pub struct Tombstone {
+
+    /// the uuid without hyphens
+    #[schema(rename = "id", value_type = String)]
+    pub uuid: Uuid,
+
+    #[schema(rename = "lastName")]
+    pub last_name: Option<String>,
+
+    /// milliseconds since the unix epoch at which upstream was first seen not to know the profile
+    #[schema(rename = "removedAt", value_type = u64)]
+    pub removed_at: SystemTime,
+}
+
+impl Serialize for Tombstone {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer 
+    {
+        let mut s = serializer.serialize_struct("Tombstone", 3)?;
+        s.serialize_field("id", &self.uuid.simple())?;
+        s.serialize_field("lastName", &self.last_name)?;
+        s.serialize_field("removedAt", &into_millis(&self.removed_at).unwrap_or_default())?;
+        s.end()
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Tombstone {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let uuid = from_column_uuid(row, "uuid")?;
+        let last_name = row.try_get("lastName")?;
+        let removed_at = from_column_millis(row, "removedAt")?.unwrap_or(SystemTime::UNIX_EPOCH);
+        Ok(Tombstone { uuid, last_name, removed_at })
+    }
+}
@@ -1,9 +1,14 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::Duration;
+use std::time::SystemTime;
 
 use sqlx::Database;
+use sqlx::FromRow;
 use sqlx::Pool;
+use sqlx::Sqlite;
 use sqlx::SqlitePool;
+use sqlx::Transaction;
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::sqlite::SqliteJournalMode;
@@ -12,9 +17,15 @@ use sqlx::sqlite::SqliteSynchronous;
 use uuid::Uuid;
 
 use self::config::DatabaseConfig;
+use self::data::ApiKey;
 use self::data::NameHistory;
 use self::data::NameHistoryElement;
+use self::data::NameOwner;
+use self::data::NameRecord;
+use self::data::Tombstone;
 use self::data::Update;
+use self::data::WebhookDelivery;
+use self::data::from_column_uuid;
 use self::data::into_argument_uuid;
 
 pub mod config;
@@ -42,11 +53,43 @@ impl NameHistoryDatabase {
             .await?;
         let r11 = sqlx::query(data::CREATE_TABLE_NAMES).execute(&pool).await?;
         let r12 = sqlx::query(data::CREATE_INDEX_NAMES).execute(&pool).await?;
+        sqlx::query(data::CREATE_INDEX_NAMES_NAME).execute(&pool).await?;
+        let (fts_exists, ): (i64, ) = sqlx::query_as(data::QUERY_NAMES_FTS_EXISTS).fetch_one(&pool).await?;
+        sqlx::query(data::CREATE_TABLE_NAMES_FTS).execute(&pool).await?;
+        sqlx::query(data::CREATE_TRIGGER_NAMES_FTS_INSERT).execute(&pool).await?;
+        sqlx::query(data::CREATE_TRIGGER_NAMES_FTS_DELETE).execute(&pool).await?;
+        sqlx::query(data::CREATE_TRIGGER_NAMES_FTS_UPDATE).execute(&pool).await?;
+        if fts_exists == 0 {
+            // index rows written before the search table existed
+            sqlx::query(data::REBUILD_NAMES_FTS).execute(&pool).await?;
+        }
         let r21 = sqlx::query(data::CREATE_TABLE_UPDATES).execute(&pool).await?;
         let r22 = sqlx::query(data::CREATE_INDEX_UPDATES).execute(&pool).await?;
+        let (missing_exists, ): (i64, ) = sqlx::query_as(data::QUERY_UPDATES_MISSING_EXISTS).fetch_one(&pool).await?;
+        if missing_exists == 0 {
+            sqlx::query(data::ADD_COLUMN_UPDATES_MISSING).execute(&pool).await?;
+        }
+        sqlx::query(data::CREATE_TABLE_TOMBSTONES).execute(&pool).await?;
+        sqlx::query(data::CREATE_TABLE_AUDIT).execute(&pool).await?;
+        sqlx::query(data::CREATE_TABLE_WEBHOOK_DELIVERIES).execute(&pool).await?;
+        sqlx::query(data::CREATE_INDEX_WEBHOOK_DELIVERIES).execute(&pool).await?;
+        sqlx::query(data::CREATE_TABLE_API_KEYS).execute(&pool).await?;
         Ok(Self { pool })
     }
 
+    /// runs a trivial query against the file, to tell whether the database is usable at all
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(data::PING).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// (active, idle) connections of the pool
+    pub fn pool_usage(&self) -> (usize, usize) {
+        let idle = self.pool.num_idle();
+        // the two counters are read apart; a connection closing in between would make `idle` the larger
+        ((self.pool.size() as usize).saturating_sub(idle), idle)
+    }
+
     pub async fn close(self) {
         self.pool.close().await;
     }
@@ -59,6 +102,82 @@ impl NameHistoryDatabase {
         Ok(q)
     }
 
+    pub async fn get_name_records(&self, uuid: &Uuid) -> Result<Vec<NameRecord>, sqlx::Error> {
+        let q = sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_RECORDS)
+            .bind(into_argument_uuid(uuid))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(q)
+    }
+
+    pub async fn get_name_histories(&self, uuids: &[Uuid]) -> Result<HashMap<Uuid, NameHistory>, sqlx::Error> {
+        let mut histories: HashMap<Uuid, NameHistory> = HashMap::with_capacity(uuids.len());
+        if uuids.is_empty() {
+            return Ok(histories);
+        }
+        let sql = data::query_name_histories(uuids.len());
+        let mut q = sqlx::query(sql.as_str());
+        for uuid in uuids {
+            q = q.bind(into_argument_uuid(uuid));
+        }
+        for row in q.fetch_all(&self.pool).await? {
+            let uuid = from_column_uuid(&row, "uuid")?;
+            let record = NameHistoryElement::from_row(&row)?;
+            histories.entry(uuid).or_default().push(record);
+        }
+        Ok(histories)
+    }
+
+    pub async fn get_name_owners(&self, name: &str) -> Result<Vec<NameOwner>, sqlx::Error> {
+        let q = sqlx::query_as::<_, NameOwner>(data::QUERY_NAME_OWNERS)
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(q)
+    }
+
+    /// case-insensitive; `cursor` is the `index` of the last row of the previous page, or 0
+    pub async fn search_names_prefix(&self, prefix: &str, cursor_name: &str, cursor_index: i64, limit: u32) -> Result<Vec<NameRecord>, sqlx::Error> {
+        let pattern = format!("{}%", data::escape_like(prefix));
+        sqlx::query_as::<_, NameRecord>(data::SEARCH_NAMES_PREFIX)
+            .bind(pattern)
+            .bind(cursor_name)
+            .bind(cursor_index)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// case-insensitive; `cursor` is the `index` of the last row of the previous page, or 0
+    pub async fn search_names_substring(&self, substring: &str, cursor: i64, limit: u32) -> Result<Vec<NameRecord>, sqlx::Error> {
+        // the trigram index can not match anything shorter than 3 characters
+        if substring.chars().count() < 3 {
+            let pattern = format!("%{}%", data::escape_like(substring));
+            sqlx::query_as::<_, NameRecord>(data::SEARCH_NAMES_SUBSTRING_SHORT)
+                .bind(pattern)
+                .bind(cursor)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+        } else {
+            sqlx::query_as::<_, NameRecord>(data::SEARCH_NAMES_SUBSTRING)
+                .bind(data::quote_fts_phrase(substring))
+                .bind(cursor)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+        }
+    }
+
+    /// rows in insertion order; `since` is the `index` of the last row already seen, or 0
+    pub async fn get_name_changes(&self, since: i64, limit: u32) -> Result<Vec<NameRecord>, sqlx::Error> {
+        sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_CHANGES)
+            .bind(since)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
     pub async fn add_name_history(&self, uuid: &Uuid, record: &NameHistoryElement, source: u32) -> Result<u64, sqlx::Error> {
         let r = if let Some(changed_to_at) = &record.changed_to_at {
             sqlx::query(data::INSERT_NAME)
@@ -86,10 +205,29 @@ impl NameHistoryDatabase {
             .await
     }
 
+    pub async fn get_updates(&self, uuids: &[Uuid]) -> Result<HashMap<Uuid, Update>, sqlx::Error> {
+        let mut updates = HashMap::with_capacity(uuids.len());
+        if uuids.is_empty() {
+            return Ok(updates);
+        }
+        let sql = data::query_updates(uuids.len());
+        let mut q = sqlx::query(sql.as_str());
+        for uuid in uuids {
+            q = q.bind(into_argument_uuid(uuid));
+        }
+        for row in q.fetch_all(&self.pool).await? {
+            let uuid = from_column_uuid(&row, "uuid")?;
+            let record = Update::from_row(&row)?;
+            updates.insert(uuid, record);
+        }
+        Ok(updates)
+    }
+
     pub async fn refresh_update(&self, uuid: &Uuid, record: &Update) -> Result<u64, sqlx::Error> {
         let r = sqlx::query(data::REFRESH_UPDATE)
             .bind(Update::into_argument_systemtime(&record.update))
             .bind(record.changed)
+            .bind(record.missing)
             .bind(into_argument_uuid(uuid))
             .execute(&self.pool)
             .await?;
@@ -101,6 +239,231 @@ impl NameHistoryDatabase {
             .bind(into_argument_uuid(uuid))
             .bind(Update::into_argument_systemtime(&record.update))
             .bind(record.changed)
+            .bind(record.missing)
+            .execute(&self.pool)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    pub async fn get_tombstone(&self, uuid: &Uuid) -> Result<Option<Tombstone>, sqlx::Error> {
+        sqlx::query_as::<_, Tombstone>(data::QUERY_TOMBSTONE)
+            .bind(into_argument_uuid(uuid))
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn add_tombstone(&self, uuid: &Uuid, last_name: Option<&str>, removed_at: &SystemTime) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::INSERT_TOMBSTONE)
+            .bind(into_argument_uuid(uuid))
+            .bind(last_name)
+            .bind(NameHistoryElement::into_argument_systemtime(removed_at))
+            .execute(&self.pool)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    pub async fn remove_tombstone(&self, uuid: &Uuid) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::DELETE_TOMBSTONE)
+            .bind(into_argument_uuid(uuid))
+            .execute(&self.pool)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    // the following mutations each write one `audit` row recording `actor` and the row before and after, in the same transaction
+
+    pub async fn admin_insert_name(&self, actor: &str, uuid: &Uuid, name: &str, changed_to_at: Option<&SystemTime>, source: u32) -> Result<NameRecord, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let index = sqlx::query(data::INSERT_NAME)
+            .bind(into_argument_uuid(uuid))
+            .bind(name)
+            .bind(changed_to_at.map(NameHistoryElement::into_argument_systemtime))
+            .bind(source)
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid();
+        let after = sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_RECORD)
+            .bind(index)
+            .fetch_one(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "insert_name", uuid, None, Some(data::into_audit_value(&after)?)).await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    pub async fn admin_edit_name(&self, actor: &str, index: i64, name: &str, changed_to_at: Option<&SystemTime>, source: u32) -> Result<Option<NameRecord>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_RECORD)
+            .bind(index)
+            .fetch_optional(&mut tx)
+            .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(None),
+        };
+        sqlx::query(data::UPDATE_NAME_RECORD)
+            .bind(name)
+            .bind(changed_to_at.map(NameHistoryElement::into_argument_systemtime))
+            .bind(source)
+            .bind(index)
+            .execute(&mut tx)
+            .await?;
+        let after = sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_RECORD)
+            .bind(index)
+            .fetch_one(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "edit_name", &before.uuid, Some(data::into_audit_value(&before)?), Some(data::into_audit_value(&after)?)).await?;
+        tx.commit().await?;
+        Ok(Some(after))
+    }
+
+    pub async fn admin_delete_name(&self, actor: &str, index: i64) -> Result<Option<NameRecord>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as::<_, NameRecord>(data::QUERY_NAME_RECORD)
+            .bind(index)
+            .fetch_optional(&mut tx)
+            .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(None),
+        };
+        sqlx::query(data::DELETE_NAME_RECORD)
+            .bind(index)
+            .execute(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "delete_name", &before.uuid, Some(data::into_audit_value(&before)?), None).await?;
+        tx.commit().await?;
+        Ok(Some(before))
+    }
+
+    pub async fn admin_reset_update(&self, actor: &str, uuid: &Uuid) -> Result<Option<Update>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as::<_, Update>(data::QUERY_UPDATE)
+            .bind(into_argument_uuid(uuid))
+            .fetch_optional(&mut tx)
+            .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(None),
+        };
+        sqlx::query(data::DELETE_UPDATE)
+            .bind(into_argument_uuid(uuid))
+            .execute(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "reset_update", uuid, Some(data::into_audit_value(&before)?), None).await?;
+        tx.commit().await?;
+        Ok(Some(before))
+    }
+
+    // api keys are not tied to a profile, so their audit rows carry the nil uuid
+
+    pub async fn admin_create_api_key(&self, actor: &str, name: &str, key_hash: &str, permissions: u32, daily_quota: Option<u32>) -> Result<ApiKey, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let index = sqlx::query(data::INSERT_API_KEY)
+            .bind(name)
+            .bind(key_hash)
+            .bind(permissions)
+            .bind(daily_quota)
+            .bind(NameHistoryElement::into_argument_systemtime(&SystemTime::now()))
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid();
+        let after = sqlx::query_as::<_, ApiKey>(data::QUERY_API_KEY)
+            .bind(index)
+            .fetch_one(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "create_api_key", &Uuid::nil(), None, Some(data::into_audit_value(&after)?)).await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    pub async fn admin_delete_api_key(&self, actor: &str, index: i64) -> Result<Option<ApiKey>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as::<_, ApiKey>(data::QUERY_API_KEY)
+            .bind(index)
+            .fetch_optional(&mut tx)
+            .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(None),
+        };
+        sqlx::query(data::DELETE_API_KEY)
+            .bind(index)
+            .execute(&mut tx)
+            .await?;
+        Self::add_audit(&mut tx, actor, "delete_api_key", &Uuid::nil(), Some(data::into_audit_value(&before)?), None).await?;
+        tx.commit().await?;
+        Ok(Some(before))
+    }
+
+    async fn add_audit(tx: &mut Transaction<'_, Sqlite>, actor: &str, action: &str, uuid: &Uuid, before: Option<String>, after: Option<String>) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::INSERT_AUDIT)
+            .bind(actor)
+            .bind(action)
+            .bind(into_argument_uuid(uuid))
+            .bind(before)
+            .bind(after)
+            .bind(NameHistoryElement::into_argument_systemtime(&SystemTime::now()))
+            .execute(tx)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    pub async fn get_api_keys(&self) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(data::QUERY_API_KEYS)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(data::QUERY_API_KEY_BY_HASH)
+            .bind(key_hash)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// counts one use of the key on `day` (days since the unix epoch);
+    /// `None` if the key is unknown, lacks `permissions` or has used up its quota for the day
+    pub async fn use_api_key(&self, key_hash: &str, day: i64, permissions: u32) -> Result<Option<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(data::USE_API_KEY)
+            .bind(key_hash)
+            .bind(day)
+            .bind(permissions)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn add_webhook_delivery(&self, target: &str, payload: &str, next_attempt_at: &SystemTime) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::INSERT_WEBHOOK_DELIVERY)
+            .bind(target)
+            .bind(payload)
+            .bind(NameHistoryElement::into_argument_systemtime(next_attempt_at))
+            .execute(&self.pool)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    pub async fn get_due_webhook_deliveries(&self, now: &SystemTime, limit: u32) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDelivery>(data::QUERY_DUE_WEBHOOK_DELIVERIES)
+            .bind(NameHistoryElement::into_argument_systemtime(now))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn reschedule_webhook_delivery(&self, index: i64, attempts: u32, next_attempt_at: &SystemTime) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::RESCHEDULE_WEBHOOK_DELIVERY)
+            .bind(attempts)
+            .bind(NameHistoryElement::into_argument_systemtime(next_attempt_at))
+            .bind(index)
+            .execute(&self.pool)
+            .await?;
+        Ok(r.rows_affected())
+    }
+
+    pub async fn remove_webhook_delivery(&self, index: i64) -> Result<u64, sqlx::Error> {
+        let r = sqlx::query(data::DELETE_WEBHOOK_DELIVERY)
+            .bind(index)
             .execute(&self.pool)
             .await?;
         Ok(r.rows_affected())
@@ -120,8 +483,16 @@ mod test {
             .enable_all()
             .build()
             .unwrap();
-        let cfg = DatabaseConfig::default();
-        rt.block_on(run_db(cfg)).unwrap();
+        // a fresh file each run, so leftovers of earlier runs can not crowd the pages the test looks at
+        let path = std::env::temp_dir().join(format!("name_history_test_{}.db", std::process::id()));
+        let remove = || for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        };
+        remove();
+        let cfg = DatabaseConfig { url: format!("sqlite://{}", path.display()), ..DatabaseConfig::default() };
+        let result = rt.block_on(run_db(cfg));
+        remove();
+        result.unwrap();
     }
 
     async fn run_db(cfg: DatabaseConfig) -> Result<(), sqlx::Error> {
@@ -137,6 +508,9 @@ mod test {
         let nh = db.get_name_history(&uuid1).await?;
         let s = serde_json::to_string(&nh).unwrap();
         println!("{}", s);
+        let owners = db.get_name_owners("NAME2").await?;
+        assert!(owners.iter().any(|o| o.uuid == uuid1 && o.name == "name2"));
+        println!("{}", serde_json::to_string(&owners).unwrap());
         let q3 = db.get_update(&uuid1).await?;
         println!("success step 3: {:?}", &q3);
         if let Some(u) = q3 {
@@ -148,6 +522,54 @@ mod test {
             let q4 = db.insert_update(&uuid1, &rec).await?;
             println!("success step 4: {}", q4);
         }
+        let records = db.get_name_records(&uuid1).await?;
+        assert_eq!(records.len(), nh.len());
+        let found = db.search_names_prefix("NAME", "", 0, 100).await?;
+        assert_eq!(found.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), vec!["name1", "name2"]);
+        let found = db.search_names_substring("AME2", 0, 100).await?;
+        assert!(found.iter().any(|r| r.uuid == uuid1 && r.name == "name2"));
+        let found = db.search_names_substring("E2", 0, 100).await?;
+        assert!(found.iter().any(|r| r.uuid == uuid1 && r.name == "name2"));
+        let changes = db.get_name_changes(0, 1000).await?;
+        assert!(changes.windows(2).all(|w| w[0].index < w[1].index));
+        let uuid2 = Uuid::parse_str("069a79f444e94726a5befca90e38aaf5").unwrap();
+        let nhs = db.get_name_histories(&[uuid1, uuid2]).await?;
+        assert_eq!(nhs.get(&uuid1).map(|h| h.len()), Some(nh.len()));
+        let us = db.get_updates(&[uuid1, uuid2]).await?;
+        assert!(us.contains_key(&uuid1));
+        println!("success step 5: {} {}", nhs.len(), us.len());
+        let inserted = db.admin_insert_name("test", &uuid2, "name3", None, 2).await?;
+        let edited = db.admin_edit_name("test", inserted.index, "name4", Some(&SystemTime::now()), 2).await?;
+        assert_eq!(edited.map(|r| r.name), Some("name4".to_string()));
+        assert!(db.admin_delete_name("test", inserted.index).await?.is_some());
+        assert!(db.admin_delete_name("test", inserted.index).await?.is_none());
+        println!("success step 6");
+        db.add_webhook_delivery("hook", "{}", &SystemTime::UNIX_EPOCH).await?;
+        let due = db.get_due_webhook_deliveries(&SystemTime::now(), 1).await?;
+        assert_eq!(due.len(), 1);
+        db.reschedule_webhook_delivery(due[0].index, 1, &SystemTime::now()).await?;
+        db.remove_webhook_delivery(due[0].index).await?;
+        println!("success step 7");
+        let key = db.admin_create_api_key("test", "test", "0123456789abcdef", data::PERMISSION_READ, Some(2)).await?;
+        assert!(db.use_api_key("0123456789abcdef", 1, data::PERMISSION_READ).await?.is_some());
+        assert!(db.use_api_key("0123456789abcdef", 1, data::PERMISSION_REFRESH).await?.is_none());
+        assert_eq!(db.use_api_key("0123456789abcdef", 1, data::PERMISSION_READ).await?.map(|k| k.usage), Some(2));
+        assert!(db.use_api_key("0123456789abcdef", 1, data::PERMISSION_READ).await?.is_none());
+        assert_eq!(db.use_api_key("0123456789abcdef", 2, data::PERMISSION_READ).await?.map(|k| k.usage), Some(1));
+        assert!(db.admin_delete_api_key("test", key.index).await?.is_some());
+        assert!(db.get_api_key_by_hash("0123456789abcdef").await?.is_none());
+        println!("success step 8");
+        db.add_tombstone(&uuid2, Some("name4"), &SystemTime::now()).await?;
+        assert_eq!(db.get_tombstone(&uuid2).await?.and_then(|t| t.last_name), Some("name4".to_string()));
+        db.remove_tombstone(&uuid2).await?;
+        assert!(db.get_tombstone(&uuid2).await?.is_none());
+        println!("success step 9");
+        // a search cursor outlives the row it was taken from
+        let first = db.search_names_prefix("NAME", "", 0, 1).await?;
+        db.admin_delete_name("test", first[0].index).await?;
+        let rest = db.search_names_prefix("NAME", first[0].name.as_str(), first[0].index, 100).await?;
+        assert_eq!(rest.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), vec!["name2"]);
+        println!("success step 10");
         Ok(())
     }
 }
\ No newline at end of file
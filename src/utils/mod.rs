@@ -1,2 +1,4 @@
 pub mod configfile;
-pub mod duration_fmt;
\ No newline at end of file
+pub mod duration_fmt;
+pub mod ratelimit;
+pub mod singleflight;
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// keys tracked before full buckets are dropped again
+const MAX_KEYS: usize = 16384;
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// token bucket per key: at most `capacity` at once, one token regained every `period`
+pub struct RateLimiter<K> {
+    capacity: f64,
+    period: Duration,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            period,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// takes one token for `key`, or returns how long to wait until one is available
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        if self.capacity < 1.0 {
+            return Err(self.period);
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_KEYS {
+            buckets.retain(|_, b| self.refilled(b, now) < self.capacity);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: self.capacity, last: now });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.period.mul_f64(1.0 - bucket.tokens))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        if self.period.is_zero() {
+            return self.capacity;
+        }
+        let gained = now.saturating_duration_since(bucket.last).as_secs_f64() / self.period.as_secs_f64();
+        (bucket.tokens + gained).min(self.capacity)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn bucket() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let t0 = Instant::now();
+        assert!(limiter.check_at(1, t0).is_ok());
+        assert!(limiter.check_at(1, t0).is_ok());
+        assert_eq!(limiter.check_at(1, t0), Err(Duration::from_secs(10)));
+        assert!(limiter.check_at(2, t0).is_ok());
+        let t1 = t0 + Duration::from_secs(5);
+        assert_eq!(limiter.check_at(1, t1), Err(Duration::from_secs(5)));
+        let t2 = t0 + Duration::from_secs(10);
+        assert!(limiter.check_at(1, t2).is_ok());
+        assert!(limiter.check_at(1, t2).is_err());
+    }
+}
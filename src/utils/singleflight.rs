@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use futures_util::future::Shared;
+
+/// runs at most one future per key at a time; callers arriving while one is running wait for it and share its output
+pub struct SingleFlight<K, V> {
+    calls: Arc<Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>>,
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+
+    pub fn new() -> Self {
+        Self {
+            calls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// `f` is only spawned if no call for `key` is running; it runs to the end on its own task even if every caller stops waiting,
+    /// and removes its key once done
+    pub async fn run<F>(&self, key: K, f: F) -> V
+    where
+        F: Future<Output = V> + Send + 'static,
+    {
+        let call = {
+            let mut calls = self.calls.lock().unwrap();
+            calls.entry(key.clone()).or_insert_with(|| {
+                let flight_calls = self.calls.clone();
+                // the entry is inserted before the lock is released, so the task cannot remove its key before it is there
+                let task = tokio::spawn(async move {
+                    let output = AssertUnwindSafe(f).catch_unwind().await;
+                    flight_calls.lock().unwrap().remove(&key);
+                    output
+                });
+                task.map(|joined| match joined {
+                    Ok(Ok(output)) => output,
+                    Ok(Err(payload)) => panic::resume_unwind(payload),
+                    Err(e) => panic::resume_unwind(e.into_panic()),
+                }).boxed().shared()
+            }).clone()
+        };
+        call.await
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use futures_util::future::join_all;
+
+    use super::*;
+
+    #[test]
+    fn coalesce() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let group = SingleFlight::<u32, u32>::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let outputs = rt.block_on(join_all((0..4).map(|_| {
+            let runs = runs.clone();
+            group.run(1, async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                runs.fetch_add(1, Ordering::SeqCst) + 1
+            })
+        })));
+        assert_eq!(outputs, vec![1, 1, 1, 1]);
+        let runs = runs.clone();
+        let output = rt.block_on(group.run(1, async move { runs.fetch_add(1, Ordering::SeqCst) + 1 }));
+        assert_eq!(output, 2);
+    }
+
+    #[test]
+    fn abandoned() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let group = SingleFlight::<u32, u32>::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let flight_runs = runs.clone();
+        let call = group.run(1, async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            flight_runs.fetch_add(1, Ordering::SeqCst) + 1
+        });
+        // the only caller gives up long before the flight is done
+        assert!(rt.block_on(async { tokio::time::timeout(Duration::from_millis(1), call).await }).is_err());
+        assert!(group.calls.lock().unwrap().contains_key(&1));
+        rt.block_on(async { tokio::time::sleep(Duration::from_millis(50)).await });
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(group.calls.lock().unwrap().is_empty());
+    }
+}
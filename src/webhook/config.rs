@@ -0,0 +1,62 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub targets: Vec<WebhookTargetConfig>,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub timeout: Duration,
+    pub max_attempts: u32,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub retry_initial: Duration,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub retry_max: Duration,
+    #[serde(with="crate::utils::duration_fmt")]
+    pub poll_interval: Duration,
+}
+
+impl Default for WebhookConfig {
+
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            timeout: Duration::from_secs(10),
+            max_attempts: 8,
+            retry_initial: Duration::from_secs(10),
+            retry_max: Duration::from_secs(3600),
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+
+#[derive(Clone,Serialize,Deserialize)]
+pub struct WebhookTargetConfig {
+    /// stored with queued deliveries to find the target again, so `url` and `secret` can change while deliveries are pending
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    /// only send changes of these accounts; all accounts if absent
+    #[serde(default)]
+    pub uuids: Option<Vec<Uuid>>,
+}
+
+/// the config is printed on startup, the secret must not be
+impl Debug for WebhookTargetConfig {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookTargetConfig")
+            .field("name", &self.name)
+            .field("url", &self.url)
+            .field("secret", &"<redacted>")
+            .field("uuids", &self.uuids)
+            .finish()
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use futures_util::future::try_join_all;
+use hmac::Hmac;
+use hmac::Mac;
+use hyper::Body;
+use hyper::Client;
+use hyper::Method;
+use hyper::Request;
+use hyper::StatusCode;
+use hyper::client::HttpConnector;
+use hyper::header;
+use hyper_tls::HttpsConnector;
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::storage::NameHistoryDatabase;
+use crate::storage::data::WebhookDelivery;
+
+use self::config::WebhookConfig;
+use self::config::WebhookTargetConfig;
+
+pub mod config;
+
+pub const SIGNATURE_HEADER: &'static str = "X-Signature-256";
+pub const DELIVERY_HEADER: &'static str = "X-Webhook-Delivery";
+
+const DELIVERY_BATCH: u32 = 32;
+
+
+pub enum WebhookError {
+    Http(hyper::http::Error),
+    Hyper(hyper::Error),
+    StatusCode(StatusCode),
+    Timeout,
+}
+
+impl fmt::Display for WebhookError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "request: {}", e),
+            Self::Hyper(e) => write!(f, "request: {}", e),
+            Self::StatusCode(s) => write!(f, "status: {}", s),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+impl From<hyper::http::Error> for WebhookError {
+
+    fn from(e: hyper::http::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+impl From<hyper::Error> for WebhookError {
+
+    fn from(e: hyper::Error) -> Self {
+        Self::Hyper(e)
+    }
+}
+
+
+/// queues name-change payloads in the database and POSTs them to the configured targets in the background
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    config: Arc<WebhookConfig>,
+    database: NameHistoryDatabase,
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    notify: Arc<Notify>,
+}
+
+impl WebhookDispatcher {
+
+    pub fn new(config: &WebhookConfig, database: NameHistoryDatabase) -> Self {
+        let mut config = config.clone();
+        // deliveries are matched to targets by name
+        let mut names = HashSet::new();
+        config.targets.retain(|target| {
+            let unique = names.insert(target.name.clone());
+            if !unique {
+                tracing::warn!("ignore webhook target {}: duplicate name", &target.name);
+            }
+            unique
+        });
+        Self {
+            config: Arc::new(config),
+            database,
+            client: Client::builder().build(HttpsConnector::new()),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub async fn enqueue(&self, uuid: &Uuid, payload: &str) -> Result<(), sqlx::Error> {
+        let now = SystemTime::now();
+        let mut queued = false;
+        for target in self.config.targets.iter().filter(|t| accepts(t, uuid)) {
+            self.database.add_webhook_delivery(target.name.as_str(), payload, &now).await?;
+            queued = true;
+        }
+        if queued {
+            self.notify.notify_one();
+        }
+        Ok(())
+    }
+
+    pub async fn run(self, mut shutdown: watch::Receiver<()>) {
+        loop {
+            // a pass may take a timeout per target; stop in the middle of it, the undelivered rows are sent on the next start
+            tokio::select! {
+                result = self.deliver_due() => {
+                    if let Err(e) = result {
+                        tracing::error!("webhook database error {}", e);
+                    }
+                }
+                _ = shutdown.changed() => break,
+            }
+            tokio::select! {
+                _ = self.notify.notified() => {}
+                _ = tokio::time::sleep(self.config.poll_interval) => {}
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+
+    async fn deliver_due(&self) -> Result<(), sqlx::Error> {
+        loop {
+            let now = SystemTime::now();
+            let due = self.database.get_due_webhook_deliveries(&now, DELIVERY_BATCH).await?;
+            if due.is_empty() {
+                return Ok(());
+            }
+            let mut by_target: Vec<(&WebhookTargetConfig, Vec<WebhookDelivery>)> = Vec::new();
+            for delivery in due {
+                let target = match self.config.targets.iter().find(|t| t.name == delivery.target) {
+                    Some(target) => target,
+                    None => {
+                        tracing::warn!("drop webhook delivery #{}: target {} no longer configured", delivery.index, &delivery.target);
+                        self.database.remove_webhook_delivery(delivery.index).await?;
+                        continue;
+                    }
+                };
+                match by_target.iter_mut().find(|(t, _)| t.name == target.name) {
+                    Some((_, deliveries)) => deliveries.push(delivery),
+                    None => by_target.push((target, vec![delivery])),
+                }
+            }
+            // an unreachable target only holds up its own deliveries
+            try_join_all(by_target.into_iter().map(|(target, deliveries)| self.deliver_to(target, deliveries))).await?;
+        }
+    }
+
+    /// in order; once one fails, the rest wait for the same retry instead of each running into the timeout
+    async fn deliver_to(&self, target: &WebhookTargetConfig, deliveries: Vec<WebhookDelivery>) -> Result<(), sqlx::Error> {
+        let mut deferred_until = None;
+        for delivery in deliveries {
+            if let Some(next_attempt_at) = deferred_until.as_ref() {
+                self.database.reschedule_webhook_delivery(delivery.index, delivery.attempts, next_attempt_at).await?;
+                continue;
+            }
+            match self.deliver(target, &delivery).await {
+                Ok(()) => {
+                    tracing::debug!("webhook delivery #{} sent to {}", delivery.index, &delivery.target);
+                    self.database.remove_webhook_delivery(delivery.index).await?;
+                }
+                Err(e) => {
+                    let attempts = delivery.attempts + 1;
+                    let next_attempt_at = SystemTime::now() + self.backoff(attempts);
+                    if attempts >= self.config.max_attempts {
+                        tracing::warn!("drop webhook delivery #{} to {} after {} attempts: {}", delivery.index, &delivery.target, attempts, e);
+                        self.database.remove_webhook_delivery(delivery.index).await?;
+                    } else {
+                        tracing::warn!("webhook delivery #{} to {} failed (attempt {}): {}", delivery.index, &delivery.target, attempts, e);
+                        self.database.reschedule_webhook_delivery(delivery.index, attempts, &next_attempt_at).await?;
+                    }
+                    deferred_until = Some(next_attempt_at);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, target: &WebhookTargetConfig, delivery: &WebhookDelivery) -> Result<(), WebhookError> {
+        let req = Request::builder()
+            .uri(target.url.as_str())
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", sign(target.secret.as_str(), delivery.payload.as_str())))
+            .header(DELIVERY_HEADER, delivery.index)
+            .body(Body::from(delivery.payload.clone()))?;
+        let resp = tokio::time::timeout(self.config.timeout, self.client.request(req)).await
+            .map_err(|_| WebhookError::Timeout)??;
+        let status_code = resp.status();
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            Err(WebhookError::StatusCode(status_code))
+        }
+    }
+
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempts - 1).unwrap_or(u32::MAX);
+        self.config.retry_initial.saturating_mul(factor).min(self.config.retry_max)
+    }
+}
+
+
+fn accepts(target: &WebhookTargetConfig, uuid: &Uuid) -> bool {
+    target.uuids.as_ref().map(|uuids| uuids.contains(uuid)).unwrap_or(true)
+}
+
+/// hex encoded HMAC-SHA256 of `payload`
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn signature() {
+        // RFC 4231, test case 2
+        assert_eq!(sign("Jefe", "what do ya want for nothing?"), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}